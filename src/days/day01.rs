@@ -1,8 +1,9 @@
-use std::collections::HashSet;
-
+use crate::problem::ToolError;
+use crate::rng::Rng;
 use crate::ProblemSolution;
 #[allow(unused_imports)]
 use aoc_parse::{parser, prelude::*};
+use counter::Counter;
 use itertools::Itertools;
 pub struct Solution {}
 
@@ -20,35 +21,121 @@ fn parse_input<L: Extend<usize> + Default, R: Extend<usize> + Default>(input: &s
         .unzip()
 }
 
-impl ProblemSolution for Solution {
-    fn solve_a(&self, input: &str) -> Option<String> {
-        let (mut left_nums, mut right_nums): (Vec<_>, Vec<_>) = parse_input(input);
-
-        left_nums.sort_unstable();
-        right_nums.sort_unstable();
+fn sorted_differences(mut left_nums: Vec<usize>, mut right_nums: Vec<usize>) -> Vec<usize> {
+    left_nums.sort_unstable();
+    right_nums.sort_unstable();
+    left_nums
+        .into_iter()
+        .zip(right_nums)
+        .map(|(l, r)| r.abs_diff(l))
+        .collect()
+}
 
-        // There are no repeated elements in the left list which makes my answer to part b valid
-        for (a, b) in left_nums.iter().tuple_windows() {
-            assert_ne!(a, b);
-        }
+// Each value contributes itself once for every (left, right) pair of copies
+fn similarities<'a>(
+    left_counts: &'a Counter<usize>,
+    right_counts: &'a Counter<usize>,
+) -> impl Iterator<Item = (usize, usize)> + 'a {
+    left_counts
+        .iter()
+        .map(|(value, n_left)| (*value, value * n_left * right_counts[value]))
+}
 
-        let total_diff: usize = left_nums
-            .into_iter()
-            .zip(right_nums.into_iter())
-            .map(|(l, r)| r.abs_diff(l))
-            .sum();
+// Quartiles of the differences and a histogram of them in ten equal buckets
+fn difference_stats(mut diffs: Vec<usize>) -> String {
+    diffs.sort_unstable();
+    let quantile = |q: usize| diffs[(diffs.len() - 1) * q / 4];
+    let mut output = format!(
+        "Differences: min {}, lower quartile {}, median {}, upper quartile {}, max {}\n",
+        quantile(0),
+        quantile(1),
+        quantile(2),
+        quantile(3),
+        quantile(4)
+    );
+    let bucket_width = diffs[diffs.len() - 1] / 10 + 1;
+    let counts: Counter<usize> = diffs.iter().map(|d| d / bucket_width).collect();
+    let most = counts.values().copied().max().unwrap_or(1);
+    for bucket in 0..10 {
+        let n = counts[&bucket];
+        output.push_str(&format!(
+            "{:>10}..{:<10} {:>6} {}\n",
+            bucket * bucket_width,
+            (bucket + 1) * bucket_width,
+            n,
+            "#".repeat((50 * n).div_ceil(most))
+        ));
+    }
+    output
+}
 
+impl ProblemSolution for Solution {
+    fn solve_a(&self, input: &str) -> Option<String> {
+        let (left_nums, right_nums): (Vec<_>, Vec<_>) = parse_input(input);
+        let total_diff: usize = sorted_differences(left_nums, right_nums).into_iter().sum();
         Some(total_diff.to_string())
     }
 
     fn solve_b(&self, input: &str) -> Option<String> {
-        let (left_nums, right_nums): (HashSet<_>, Vec<_>) = parse_input(input);
-
-        let similarity: usize = right_nums
-            .into_iter()
-            .filter(|n| left_nums.contains(n))
+        let (left_counts, right_counts): (Counter<_>, Counter<_>) = parse_input(input);
+        let similarity: usize = similarities(&left_counts, &right_counts)
+            .map(|(_value, similarity)| similarity)
             .sum();
-
         Some(similarity.to_string())
     }
+
+    // stats [top] : distribution of the paired differences, and the `top` values (default 5)
+    // contributing most to the similarity score
+    fn run_tool(&self, input: &str, args: &[String]) -> Result<String, ToolError> {
+        if args[0] != "stats" {
+            return Err(ToolError::Unknown);
+        }
+        let top = match args.get(1) {
+            Some(arg) => arg
+                .parse()
+                .map_err(|_e| ToolError::BadArgs(format!("Could not parse \"{arg}\"")))?,
+            None => 5,
+        };
+        let (left_nums, right_nums): (Vec<_>, Vec<_>) = parse_input(input);
+        if left_nums.is_empty() {
+            return Err(ToolError::BadArgs("No numbers in input".to_string()));
+        }
+        let left_counts: Counter<_> = left_nums.iter().copied().collect();
+        let right_counts: Counter<_> = right_nums.iter().copied().collect();
+        let mut output = difference_stats(sorted_differences(left_nums, right_nums));
+
+        let mut top_matches: Vec<_> = similarities(&left_counts, &right_counts)
+            .filter(|(_value, similarity)| *similarity > 0)
+            .collect();
+        top_matches.sort_by_key(|(value, similarity)| (std::cmp::Reverse(*similarity), *value));
+        output.push_str(&format!(
+            "{} values appear in both lists\n",
+            top_matches.len()
+        ));
+        for (value, similarity) in top_matches.into_iter().take(top) {
+            output.push_str(&format!(
+                "{value}: {} left x {} right, similarity {similarity}\n",
+                left_counts[&value], right_counts[&value]
+            ));
+        }
+        Ok(output)
+    }
+
+    // size lines, drawing values from a pool small enough that they repeat within and across lists
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        let pool: Vec<usize> = (0..size.div_ceil(2))
+            .map(|_| rng.range(10000..=99999))
+            .collect();
+        let draw = |rng: &mut Rng| {
+            if rng.one_in(3) {
+                rng.range(10000..=99999)
+            } else {
+                *rng.pick(&pool)
+            }
+        };
+        let lines: Vec<String> = (0..size)
+            .map(|_| format!("{}   {}\n", draw(rng), draw(rng)))
+            .collect();
+        Some(lines.concat())
+    }
 }
@@ -1,9 +1,27 @@
+use crate::problem::ToolError;
+use crate::rng::Rng;
 use crate::ProblemSolution;
 #[allow(unused_imports)]
 use aoc_parse::{parser, prelude::*};
 use itertools::Itertools;
 pub struct Solution {}
 
+#[derive(Clone, Copy)]
+enum Direction {
+    Increasing,
+    Decreasing,
+}
+
+impl Direction {
+    // delta is the earlier level minus the later one
+    fn allows(self, delta: isize) -> bool {
+        match self {
+            Direction::Increasing => (-3..=-1).contains(&delta),
+            Direction::Decreasing => (1..=3).contains(&delta),
+        }
+    }
+}
+
 trait SafetyCheck {
     fn deltas<'a>(&'a self) -> impl Iterator<Item = isize> + 'a;
 
@@ -18,6 +36,11 @@ trait SafetyCheck {
     fn is_safe(&self) -> bool {
         self.is_monotonic() && self.is_bounded()
     }
+
+    // Index of the first step (from level idx to idx + 1) not going the right way by 1 to 3
+    fn first_bad_step(&self, direction: Direction) -> Option<usize> {
+        self.deltas().position(|d| !direction.allows(d))
+    }
 }
 
 struct Report(Vec<usize>);
@@ -39,8 +62,56 @@ impl Report {
         }
     }
 
-    fn has_safe_mask(&self) -> bool {
-        (0..self.0.len()).any(|idx| self.mask(idx).is_safe())
+    // Some(None) if already safe, Some(Some(idx)) if removing level idx makes it safe.
+    // Whichever level is removed, the levels either side of the first bad step can't both
+    // stay, so only those two removals need trying, keeping this linear in the report length
+    fn dampened(&self) -> Option<Option<usize>> {
+        let directions = [Direction::Increasing, Direction::Decreasing];
+        if directions
+            .iter()
+            .any(|dir| self.first_bad_step(*dir).is_none())
+        {
+            return Some(None);
+        }
+        directions.into_iter().find_map(|dir| {
+            let bad_step = self.first_bad_step(dir)?;
+            [bad_step, bad_step + 1]
+                .into_iter()
+                .find(|idx| self.mask(*idx).first_bad_step(dir).is_none())
+                .map(Some)
+        })
+    }
+
+    // What makes the report unsafe and how (if at all) the dampener fixes it
+    fn explain(&self) -> Option<String> {
+        if self.is_safe() {
+            return None;
+        }
+        let levels = &self.0;
+        let first_direction = self.deltas().find(|d| *d != 0).map(|d| d.signum());
+        // The first step that is flat, too big or against the report's direction
+        let (idx, delta) = self
+            .deltas()
+            .enumerate()
+            .find(|(_idx, d)| *d == 0 || d.abs() > 3 || Some(d.signum()) != first_direction)?;
+        let (from, to) = (levels[idx], levels[idx + 1]);
+        let problem = if delta == 0 {
+            format!("level {} ({to}) doesn't change", idx + 2)
+        } else if delta.abs() > 3 {
+            format!("level {} jumps from {from} to {to}", idx + 2)
+        } else {
+            let turn = if delta < 0 { "increases" } else { "decreases" };
+            format!("level {} {turn} from {from} to {to}", idx + 2)
+        };
+        let fix = match self.dampened() {
+            Some(Some(removed)) => format!(
+                "removing level {} ({}) fixes it",
+                removed + 1,
+                levels[removed]
+            ),
+            _ => "no single removal fixes it".to_string(),
+        };
+        Some(format!("{problem}, {fix}"))
     }
 }
 
@@ -82,8 +153,47 @@ impl ProblemSolution for Solution {
         let reports = parse_input(input);
         let n_safe = reports
             .iter()
-            .filter(|rep| rep.is_safe() || rep.has_safe_mask())
+            .filter(|rep| rep.dampened().is_some())
             .count();
         Some(n_safe.to_string())
     }
+
+    // explain : for each unsafe report, the first level that breaks the rules and which
+    // removal, if any, makes it safe
+    fn run_tool(&self, input: &str, args: &[String]) -> Result<String, ToolError> {
+        if args[0] != "explain" {
+            return Err(ToolError::Unknown);
+        }
+        let reports = parse_input(input);
+        let mut output = String::new();
+        for (idx, report) in reports.iter().enumerate() {
+            if let Some(explanation) = report.explain() {
+                let levels = report.0.iter().join(" ");
+                output.push_str(&format!("Report {} ({levels}): {explanation}\n", idx + 1));
+            }
+        }
+        Ok(output)
+    }
+
+    // size reports of 5 to 8 levels, mostly steady climbs or descents with the odd bad step
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        let mut output = String::new();
+        for _ in 0..size {
+            let increasing = rng.one_in(2);
+            let mut level = rng.range(30..=70) as isize;
+            let mut levels = vec![];
+            for _ in 0..rng.range(5..=8) {
+                levels.push(level.to_string());
+                let step = if rng.one_in(10) {
+                    rng.range(0..=8) as isize - 4
+                } else {
+                    rng.range(1..=3) as isize
+                };
+                level += if increasing { step } else { -step };
+            }
+            output.push_str(&levels.join(" "));
+            output.push('\n');
+        }
+        Some(output)
+    }
 }
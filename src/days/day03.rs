@@ -1,5 +1,6 @@
 use std::process::Command;
 
+use crate::rng::Rng;
 use crate::ProblemSolution;
 #[allow(unused_imports)]
 use aoc_parse::{parser, prelude::*};
@@ -7,6 +8,22 @@ pub struct Solution {}
 
 // Fancied doing today in awk
 
+// Fragments that look a bit like instructions but aren't
+const JUNK: [&str; 12] = [
+    "mul[3,7]",
+    "mul(4*",
+    "mul ( 2 , 4 )",
+    "?mul(8,5",
+    "where()",
+    "from()",
+    "select()",
+    ")'",
+    "%&",
+    "mul(32,64]",
+    "don't",
+    "do(",
+];
+
 impl ProblemSolution for Solution {
     fn solve_a(&self, _input: &str) -> Option<String> {
         String::from_utf8(
@@ -31,4 +48,25 @@ impl ProblemSolution for Solution {
         .ok()
         .map(|s| s.trim().to_string())
     }
+
+    // size instructions (mostly muls, some do() and don't()) hidden among junk, 50 to a line
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        let mut output = String::new();
+        for idx in 0..size {
+            let instruction = match rng.range(0..=9) {
+                0 => "do()".to_string(),
+                1 => "don't()".to_string(),
+                _ => format!("mul({},{})", rng.range(1..=999), rng.range(1..=999)),
+            };
+            output.push_str(&instruction);
+            if rng.one_in(2) {
+                let junk = rng.pick(&JUNK);
+                output.push_str(junk);
+            }
+            if idx % 50 == 49 || idx == size - 1 {
+                output.push('\n');
+            }
+        }
+        Some(output)
+    }
 }
@@ -1,19 +1,122 @@
+use std::collections::HashSet;
+
+use crate::problem::ToolError;
+use crate::rng::Rng;
 use crate::ProblemSolution;
 #[allow(unused_imports)]
 use aoc_parse::{parser, prelude::*};
+use colored::Colorize;
 pub struct Solution {}
 
-const OFFSETS: [(isize, isize); 8] = [
-    (-1, 0),
-    (-1, -1),
-    (0, -1),
-    (1, -1),
-    (1, 0),
-    (1, 1),
-    (0, 1),
-    (-1, 1),
+const OFFSETS: [((isize, isize), &str); 8] = [
+    ((-1, 0), "N"),
+    ((-1, -1), "NW"),
+    ((0, -1), "W"),
+    ((1, -1), "SW"),
+    ((1, 0), "S"),
+    ((1, 1), "SE"),
+    ((0, 1), "E"),
+    ((-1, 1), "NE"),
 ];
 
+// Letters at offsets from an anchor cell, leaving out wildcards
+#[derive(PartialEq)]
+struct Stencil(Vec<((isize, isize), char)>);
+
+impl Stencil {
+    // Anchored at the first letter in reading order, so matches are found right up to the
+    // edge of the grid however many wildcards surround the letters
+    fn anchored(mut cells: Vec<((isize, isize), char)>) -> Self {
+        cells.sort();
+        let Some(((i0, j0), _c)) = cells.first().copied() else {
+            return Self(cells);
+        };
+        Self(
+            cells
+                .into_iter()
+                .map(|((i, j), c)| ((i - i0, j - j0), c))
+                .collect(),
+        )
+    }
+
+    // Turn a quarter clockwise
+    fn rotate(&self) -> Self {
+        Self::anchored(self.0.iter().map(|((i, j), c)| ((*j, -*i), *c)).collect())
+    }
+}
+
+// Something to search for, in each of the orientations it may appear in
+struct Pattern {
+    name: String,
+    orientations: Vec<(&'static str, Stencil)>,
+}
+
+impl Pattern {
+    // Orientations covering the same letters in the same places as an earlier one are dropped,
+    // otherwise symmetric patterns and palindromes would be found more than once in one place
+    fn new(name: String, candidates: impl Iterator<Item = (&'static str, Stencil)>) -> Self {
+        let mut orientations: Vec<(&'static str, Stencil)> = vec![];
+        for (orientation, stencil) in candidates {
+            let shape = Stencil::anchored(stencil.0.clone());
+            if !orientations
+                .iter()
+                .any(|(_name, other)| Stencil::anchored(other.0.clone()) == shape)
+            {
+                orientations.push((orientation, stencil));
+            }
+        }
+        Self { name, orientations }
+    }
+
+    // A word reading in a straight line in any of the eight directions, anchored at its
+    // first letter
+    fn word(word: &str) -> Self {
+        let candidates = OFFSETS.iter().map(|((di, dj), name)| {
+            let cells = (0..)
+                .zip(word.chars())
+                .map(|(k, c)| ((k * di, k * dj), c))
+                .collect();
+            (*name, Stencil(cells))
+        });
+        Self::new(word.to_string(), candidates)
+    }
+
+    // A 2D pattern where '.' matches anything. Appears in any of its (distinct) quarter turns
+    fn stencil(rows: &[&str]) -> Self {
+        let cells: Vec<_> = (0..)
+            .zip(rows)
+            .flat_map(|(i, row)| {
+                (0..)
+                    .zip(row.chars())
+                    .filter(|(_j, c)| *c != '.')
+                    .map(move |(j, c)| ((i, j), c))
+            })
+            .collect();
+        let mut stencil = Stencil::anchored(cells);
+        let candidates = ["0°", "90°", "180°", "270°"].into_iter().map(|name| {
+            let rotated = stencil.rotate();
+            (name, std::mem::replace(&mut stencil, rotated))
+        });
+        Self::new(rows.join("/"), candidates)
+    }
+
+    // Words like "XMAS", or stencils with rows separated by '/' like "M.S/.A./M.S".
+    // None if there are no letters, as that would match everywhere
+    fn parse(s: &str) -> Option<Self> {
+        let pattern = if s.contains('/') {
+            Self::stencil(&s.split('/').collect::<Vec<_>>())
+        } else {
+            Self::word(s)
+        };
+        (!pattern.orientations[0].1 .0.is_empty()).then_some(pattern)
+    }
+}
+
+struct Match {
+    anchor: (isize, isize),
+    orientation: usize,
+}
+
 struct Grid(Vec<Vec<char>>);
 
 impl Grid {
@@ -24,20 +127,11 @@ impl Grid {
         self.0.get(pos.0 as usize)?.get(pos.1 as usize).copied()
     }
 
-    fn check_for_string(&self, pos: (isize, isize), dir: (isize, isize), check: &str) -> bool {
-        match (self.get(pos), check.chars().nth(0)) {
-            // Nothing left to check
-            (_, None) => true,
-            // Ran out of bounds but got string left to check
-            (None, Some(_)) => false,
-            (Some(c1), Some(c2)) => {
-                if c1 == c2 {
-                    self.check_for_string((pos.0 + dir.0, pos.1 + dir.1), dir, &check[1..])
-                } else {
-                    false
-                }
-            }
-        }
+    fn check_for_stencil(&self, anchor: (isize, isize), stencil: &Stencil) -> bool {
+        stencil
+            .0
+            .iter()
+            .all(|((i, j), c)| self.get((anchor.0 + i, anchor.1 + j)) == Some(*c))
     }
 
     fn iter_pos(&self) -> impl Iterator<Item = (isize, isize)> {
@@ -46,20 +140,37 @@ impl Grid {
         (0..dim1).flat_map(move |i| (0..dim2).map(move |j| (i as isize, j as isize)))
     }
 
-    // offset_idx is an index into OFFSETS
-    // The matching direction for an x-mas appears two later
-    fn check_for_x_mas(&self, pos: (isize, isize), offset_idx: usize) -> bool {
-        const MAS: &str = "MAS";
-
-        let rotate_by_2_idx = (offset_idx + 2) % 8;
-        let dir0 = OFFSETS[offset_idx];
-        let dir1 = OFFSETS[rotate_by_2_idx];
-
-        let mas0_start = (pos.0 - dir0.0, pos.1 - dir0.1);
-        let mas1_start = (pos.0 - dir1.0, pos.1 - dir1.1);
+    fn find(&self, pattern: &Pattern) -> Vec<Match> {
+        self.iter_pos()
+            .flat_map(|anchor| {
+                pattern
+                    .orientations
+                    .iter()
+                    .enumerate()
+                    .filter(move |(_idx, (_name, stencil))| self.check_for_stencil(anchor, stencil))
+                    .map(move |(orientation, _)| Match {
+                        anchor,
+                        orientation,
+                    })
+            })
+            .collect()
+    }
 
-        self.check_for_string(mas0_start, dir0, &MAS)
-            && self.check_for_string(mas1_start, dir1, &MAS)
+    // The grid with every letter that is part of a match highlighted
+    fn render(&self, highlighted: &HashSet<(isize, isize)>) -> String {
+        let mut output = String::new();
+        for (i, row) in self.0.iter().enumerate() {
+            for (j, c) in row.iter().enumerate() {
+                let cell = if highlighted.contains(&(i as isize, j as isize)) {
+                    c.to_string().bold().yellow()
+                } else {
+                    c.to_string().dimmed()
+                };
+                output.push_str(&cell.to_string());
+            }
+            output.push('\n');
+        }
+        output
     }
 }
 
@@ -70,31 +181,65 @@ fn parse_input(input: &str) -> Grid {
 
 impl ProblemSolution for Solution {
     fn solve_a(&self, input: &str) -> Option<String> {
-        const XMAS: &str = "XMAS";
-
         let grid = parse_input(input);
-        let n_matches = grid
-            .iter_pos()
-            .flat_map(|pos| OFFSETS.iter().copied().map(move |off| (pos, off)))
-            .filter(|(pos, off)| grid.check_for_string(*pos, *off, &XMAS))
-            .count();
+        let n_matches = grid.find(&Pattern::word("XMAS")).len();
         Some(n_matches.to_string())
     }
 
     fn solve_b(&self, input: &str) -> Option<String> {
         let grid = parse_input(input);
-        let diagonal_offset_idxs = [1, 3, 5, 7];
+        let n_matches = grid.find(&Pattern::stencil(&["M.S", ".A.", "M.S"])).len();
+        Some(n_matches.to_string())
+    }
+
+    // find <pattern>... : every match of each word or stencil (rows separated by /, . is a wildcard)
+    // render <pattern>... : the grid with every match highlighted
+    fn run_tool(&self, input: &str, args: &[String]) -> Result<String, ToolError> {
+        if args[0] != "find" && args[0] != "render" {
+            return Err(ToolError::Unknown);
+        }
+        if args.len() < 2 {
+            return Err(ToolError::BadArgs("Expected a word or pattern".to_string()));
+        }
+        let grid = parse_input(input);
+        let mut output = String::new();
+        let mut highlighted = HashSet::new();
+        for arg in &args[1..] {
+            let pattern = Pattern::parse(arg).ok_or_else(|| {
+                ToolError::BadArgs(format!("Pattern \"{arg}\" has no letters to match"))
+            })?;
+            let matches = grid.find(&pattern);
+            output.push_str(&format!("{}: {} matches\n", pattern.name, matches.len()));
+            for m in matches {
+                let (name, stencil) = &pattern.orientations[m.orientation];
+                if args[0] == "find" {
+                    output.push_str(&format!("  at {:?} facing {name}\n", m.anchor));
+                }
+                highlighted.extend(
+                    stencil
+                        .0
+                        .iter()
+                        .map(|((i, j), _c)| (m.anchor.0 + i, m.anchor.1 + j)),
+                );
+            }
+        }
+        if args[0] == "render" {
+            output.push_str(&grid.render(&highlighted));
+        }
+        Ok(output)
+    }
 
-        let n_matches = grid
-            .iter_pos()
-            .flat_map(|pos| {
-                diagonal_offset_idxs
-                    .iter()
-                    .copied()
-                    .map(move |idx| (pos, idx))
+    // A size x size grid of the letters in XMAS
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        let letters = ['X', 'M', 'A', 'S'];
+        let rows: Vec<String> = (0..size)
+            .map(|_| {
+                (0..size)
+                    .map(|_| *rng.pick(&letters))
+                    .chain(['\n'])
+                    .collect()
             })
-            .filter(|(pos, idx)| grid.check_for_x_mas(*pos, *idx))
-            .count();
-        Some(n_matches.to_string())
+            .collect();
+        Some(rows.concat())
     }
 }
@@ -1,60 +1,144 @@
+use std::fmt::Display;
+
+use crate::problem::ToolError;
+use crate::rng::Rng;
 use crate::ProblemSolution;
 #[allow(unused_imports)]
 use aoc_parse::{parser, prelude::*};
+use rustc_hash::{FxHashMap, FxHashSet};
 pub struct Solution {}
 
-#[derive(PartialEq, Eq)]
 struct Rule(usize, usize);
 
-struct RuleSet(Vec<Rule>);
+// For each page, the pages that must come after it
+struct RuleSet(FxHashMap<usize, FxHashSet<usize>>);
 
 impl RuleSet {
-    // Return the index of a minimal element, starting at starting_idx
-    fn minimize(&self, amongst: &[usize], starting_idx: usize) -> usize {
-        let starting_value = amongst[starting_idx];
-        for (other_idx, other_value) in amongst.iter().enumerate() {
-            if self.0.contains(&Rule(*other_value, starting_value)) {
-                return self.minimize(amongst, other_idx);
-            }
+    fn from_rules(rules: Vec<Rule>) -> Self {
+        let mut after: FxHashMap<usize, FxHashSet<usize>> = FxHashMap::default();
+        for Rule(before, later) in rules {
+            after.entry(before).or_default().insert(later);
         }
-        starting_idx
+        Self(after)
     }
-}
-
-struct Update(Vec<usize>);
 
-// Have to do custom sort because we only have a partial order
-// Essentially just find a minimal element and put it first then recurse
-fn sort(numbers: &mut [usize], rule_set: &RuleSet) {
-    if numbers.len() == 0 {
-        return;
+    fn must_precede(&self, before: usize, later: usize) -> bool {
+        self.0
+            .get(&before)
+            .is_some_and(|after| after.contains(&later))
     }
-    let minimal_idx = rule_set.minimize(&numbers, 0);
-    numbers.swap(0, minimal_idx);
-    sort(&mut numbers[1..], rule_set)
 }
 
-impl Update {
-    fn satisfies(&self, rule: &Rule) -> bool {
-        let Some(lhs) = self.0.iter().position(|e| *e == rule.0) else {
-            return true;
-        };
-        let Some(rhs) = self.0.iter().position(|e| *e == rule.1) else {
-            return true;
+enum OrderError {
+    // Each page must come before the next, and the last before the first
+    Cycle(Vec<usize>),
+    // No rule decides which of these pages comes first
+    Ambiguous(Vec<usize>),
+    // The page appears more than once, so its position can't be decided
+    Duplicate(usize),
+}
+
+impl Display for OrderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = |pages: &[usize], sep: &str| {
+            pages
+                .iter()
+                .map(|page| page.to_string())
+                .collect::<Vec<_>>()
+                .join(sep)
         };
-        lhs <= rhs
+        match self {
+            OrderError::Cycle(pages) => {
+                write!(
+                    f,
+                    "Rules form a cycle: {} -> {}",
+                    join(pages, " -> "),
+                    pages[0]
+                )
+            }
+            OrderError::Ambiguous(pages) => {
+                write!(f, "No rule orders pages {}", join(pages, ", "))
+            }
+            OrderError::Duplicate(page) => write!(f, "Page {page} appears more than once"),
+        }
     }
+}
+
+struct Update(Vec<usize>);
 
+impl Update {
     fn satisfies_set(&self, rule_set: &RuleSet) -> bool {
-        rule_set.0.iter().all(|rule| self.satisfies(rule))
+        self.0.iter().enumerate().all(|(idx, page)| {
+            self.0[idx + 1..]
+                .iter()
+                .all(|later| !rule_set.must_precede(*later, *page))
+        })
     }
 
     fn middle_page(&self) -> usize {
         self.0[self.0.len() / 2]
     }
 
-    fn sort(&mut self, rule_set: &RuleSet) {
-        sort(&mut self.0, rule_set)
+    // Kahn's algorithm, only considering rules between pages in this update
+    fn sort(&mut self, rule_set: &RuleSet) -> Result<(), OrderError> {
+        let mut pages: FxHashSet<usize> = FxHashSet::default();
+        if let Some(page) = self.0.iter().find(|page| !pages.insert(**page)) {
+            return Err(OrderError::Duplicate(*page));
+        }
+        let mut n_before: FxHashMap<usize, usize> = pages.iter().map(|page| (*page, 0)).collect();
+        for page in &pages {
+            for later in rule_set.0.get(page).into_iter().flatten() {
+                if let Some(n) = n_before.get_mut(later) {
+                    *n += 1;
+                }
+            }
+        }
+        let mut ready: Vec<usize> = pages.iter().copied().filter(|p| n_before[p] == 0).collect();
+        let mut sorted = vec![];
+        while let Some(page) = ready.pop() {
+            if !ready.is_empty() {
+                ready.push(page);
+                ready.sort();
+                return Err(OrderError::Ambiguous(ready));
+            }
+            sorted.push(page);
+            n_before.remove(&page);
+            for later in rule_set.0.get(&page).into_iter().flatten() {
+                if let Some(n) = n_before.get_mut(later) {
+                    *n -= 1;
+                    if *n == 0 {
+                        ready.push(*later);
+                    }
+                }
+            }
+        }
+        if !n_before.is_empty() {
+            let remaining: FxHashSet<usize> = n_before.into_keys().collect();
+            return Err(OrderError::Cycle(find_cycle(&remaining, rule_set)));
+        }
+        self.0 = sorted;
+        Ok(())
+    }
+}
+
+// Every remaining page has an earlier page that also remains, so walking
+// backwards must eventually revisit a page
+fn find_cycle(remaining: &FxHashSet<usize>, rule_set: &RuleSet) -> Vec<usize> {
+    let mut walk = vec![*remaining.iter().min().unwrap()];
+    loop {
+        let page = *walk.last().unwrap();
+        let before = remaining
+            .iter()
+            .copied()
+            .filter(|before| rule_set.must_precede(*before, page))
+            .min()
+            .unwrap();
+        if let Some(start) = walk.iter().position(|p| *p == before) {
+            let mut cycle = walk.split_off(start);
+            cycle.reverse();
+            return cycle;
+        }
+        walk.push(before);
     }
 }
 
@@ -62,7 +146,7 @@ fn parse(input: &str) -> (RuleSet, Vec<Update>) {
     let rule = parser!(a:usize "|" b:usize => Rule(a, b));
     let update = parser!(pgs:repeat_sep(usize, ",") => Update(pgs));
     let p = parser!(
-        section(rs:lines(rule) => RuleSet(rs))
+        section(rs:lines(rule) => RuleSet::from_rules(rs))
         section(lines(update))
     );
     p.parse(input).unwrap()
@@ -81,14 +165,64 @@ impl ProblemSolution for Solution {
 
     fn solve_b(&self, input: &str) -> Option<String> {
         let (rule_set, updates) = parse(input);
-        let answer: usize = updates
+        // Rules that can't sort an update leave the puzzle without an answer,
+        // the check tool says why
+        let answer: Result<usize, OrderError> = updates
             .into_iter()
             .filter(|up| !up.satisfies_set(&rule_set))
             .map(|mut up| {
-                up.sort(&rule_set);
-                up.middle_page()
+                up.sort(&rule_set)?;
+                Ok(up.middle_page())
             })
             .sum();
-        Some(answer.to_string())
+        answer.ok().map(|answer| answer.to_string())
+    }
+
+    // check : whether each update is in order, and if not its sorted order or why it can't be sorted
+    fn run_tool(&self, input: &str, args: &[String]) -> Result<String, ToolError> {
+        if args[0] != "check" {
+            return Err(ToolError::Unknown);
+        }
+        let (rule_set, updates) = parse(input);
+        let mut output = String::new();
+        for (idx, mut update) in updates.into_iter().enumerate() {
+            let in_order = update.satisfies_set(&rule_set);
+            let result = match update.sort(&rule_set) {
+                Ok(()) if in_order => "in order".to_string(),
+                Ok(()) => format!("sorts to {:?}", update.0),
+                Err(e) => e.to_string(),
+            };
+            output.push_str(&format!("Update {}: {result}\n", idx + 1));
+        }
+        Ok(output)
+    }
+
+    // Rules between every pair of 49 pages, taken from a random total order so every update
+    // sorts uniquely, then size updates of 5 to 23 pages, about half of them already in order
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        let mut pages: Vec<usize> = (10..=99).collect();
+        rng.shuffle(&mut pages);
+        pages.truncate(49);
+        let mut rules = vec![];
+        for (idx, before) in pages.iter().enumerate() {
+            for later in &pages[idx + 1..] {
+                rules.push(format!("{before}|{later}\n"));
+            }
+        }
+        rng.shuffle(&mut rules);
+        let mut output = rules.concat();
+        output.push('\n');
+        for _ in 0..size {
+            let mut idxs: Vec<usize> = (0..pages.len()).collect();
+            rng.shuffle(&mut idxs);
+            idxs.truncate(2 * rng.range(2..=11) + 1);
+            if rng.one_in(2) {
+                idxs.sort();
+            }
+            let update: Vec<String> = idxs.iter().map(|idx| pages[*idx].to_string()).collect();
+            output.push_str(&update.join(","));
+            output.push('\n');
+        }
+        Some(output)
     }
 }
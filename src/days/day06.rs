@@ -1,8 +1,15 @@
 use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
 
+use crate::problem::ToolError;
+use crate::rng::Rng;
 use crate::ProblemSolution;
 #[allow(unused_imports)]
 use aoc_parse::{parser, prelude::*};
+use colored::{ColoredString, Colorize};
 pub struct Solution {}
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -48,6 +55,15 @@ impl Direction {
     fn rotate(self) -> Self {
         (((self as usize) + 1) % 4).try_into().unwrap()
     }
+
+    fn arrow(&self) -> char {
+        match self {
+            Direction::Up => '^',
+            Direction::Right => '>',
+            Direction::Down => 'v',
+            Direction::Left => '<',
+        }
+    }
 }
 
 struct Grid(Vec<Vec<State>>);
@@ -86,10 +102,6 @@ impl Walker {
     fn get_state(&self, position: &(usize, usize)) -> State {
         self.grid.0[position.0][position.1]
     }
-    fn set_state(&mut self, position: &(usize, usize), new_state: State) {
-        self.grid.0[position.0][position.1] = new_state;
-    }
-
     fn update(&mut self) -> bool {
         let Some(new_pos) = self.get_pos_in_front() else {
             return false;
@@ -102,48 +114,209 @@ impl Walker {
                 self.direction = self.direction.rotate();
             }
         }
-        return true;
+        true
     }
 
-    fn test_for_loop(&mut self) -> bool {
-        let init_pos = self.position;
-        let init_dir = self.direction;
-
-        let Some(pos_in_front) = self.get_pos_in_front() else {
-            // Cannot alter grid to force loop
-            return false;
-        };
-        if matches!(self.get_state(&pos_in_front), State::Occupado) {
-            // Cannot alter grid to force loop
-            return false;
-        }
+    fn bounds(&self) -> (usize, usize) {
+        (self.grid.0.len(), self.grid.0[0].len())
+    }
+}
 
-        // Alter grid
-        self.set_state(&pos_in_front, State::Occupado);
+// For every cell and direction, where the guard stops before the next obstacle
+// (None if they walk off the grid instead)
+struct JumpTable {
+    width: usize,
+    stops: Vec<[Option<(usize, usize)>; 4]>,
+}
 
-        //println!("Running a test at {:?} {:?}", self.position, self.direction);
+impl JumpTable {
+    fn build(grid: &Grid) -> Self {
+        let (height, width) = (grid.0.len(), grid.0[0].len());
+        let mut stops = vec![[None; 4]; height * width];
+        let occupied = |i: usize, j: usize| matches!(grid.0[i][j], State::Occupado);
+        for j in 0..width {
+            let mut stop = None;
+            for i in 0..height {
+                if occupied(i, j) {
+                    stop = Some((i + 1, j));
+                } else {
+                    stops[i * width + j][Direction::Up as usize] = stop;
+                }
+            }
+            let mut stop = None;
+            for i in (0..height).rev() {
+                if occupied(i, j) {
+                    stop = i.checked_sub(1).map(|i| (i, j));
+                } else {
+                    stops[i * width + j][Direction::Down as usize] = stop;
+                }
+            }
+        }
+        for i in 0..height {
+            let mut stop = None;
+            for j in 0..width {
+                if occupied(i, j) {
+                    stop = Some((i, j + 1));
+                } else {
+                    stops[i * width + j][Direction::Left as usize] = stop;
+                }
+            }
+            let mut stop = None;
+            for j in (0..width).rev() {
+                if occupied(i, j) {
+                    stop = j.checked_sub(1).map(|j| (i, j));
+                } else {
+                    stops[i * width + j][Direction::Right as usize] = stop;
+                }
+            }
+        }
+        Self { width, stops }
+    }
 
-        // Test for loop
-        let mut seen_states = HashSet::new();
-        seen_states.insert((self.position, self.direction));
+    // As above, but with an extra obstacle placed at insert
+    fn stop_with_insert(
+        &self,
+        pos: (usize, usize),
+        direction: Direction,
+        insert: (usize, usize),
+    ) -> Option<(usize, usize)> {
+        let stop = self.stops[pos.0 * self.width + pos.1][direction as usize];
+        // Does the insert lie strictly ahead of us, and no further than the stop?
+        let blocks = match direction {
+            Direction::Up => {
+                insert.1 == pos.1 && insert.0 < pos.0 && stop.is_none_or(|s| insert.0 >= s.0)
+            }
+            Direction::Down => {
+                insert.1 == pos.1 && insert.0 > pos.0 && stop.is_none_or(|s| insert.0 <= s.0)
+            }
+            Direction::Left => {
+                insert.0 == pos.0 && insert.1 < pos.1 && stop.is_none_or(|s| insert.1 >= s.1)
+            }
+            Direction::Right => {
+                insert.0 == pos.0 && insert.1 > pos.1 && stop.is_none_or(|s| insert.1 <= s.1)
+            }
+        };
+        if !blocks {
+            return stop;
+        }
+        let delta = direction.delta();
+        Some((
+            (insert.0 as isize - delta[0]) as usize,
+            (insert.1 as isize - delta[1]) as usize,
+        ))
+    }
 
-        let found_loop = loop {
-            if !self.update() {
+    // Jump from obstacle to obstacle, only recording the states where the guard turns
+    fn test_for_loop(
+        &self,
+        mut pos: (usize, usize),
+        mut direction: Direction,
+        insert: (usize, usize),
+        seen: &mut TurnSet,
+    ) -> bool {
+        seen.clear();
+        loop {
+            let Some(stop) = self.stop_with_insert(pos, direction, insert) else {
                 // Can't loop if we escape
-                break false;
-            }
-            if !seen_states.insert((self.position, self.direction)) {
+                return false;
+            };
+            pos = stop;
+            direction = direction.rotate();
+            if !seen.insert(pos, direction) {
                 // Seen this state before, found a loop
-                break true;
+                return true;
             }
+        }
+    }
+}
+
+// Dense bitset of (position, direction) states
+// Remembers which words it has touched so clearing is cheap
+struct TurnSet {
+    width: usize,
+    bits: Vec<u64>,
+    touched: Vec<usize>,
+}
+
+impl TurnSet {
+    fn init(bounds: (usize, usize)) -> Self {
+        let n_states = bounds.0 * bounds.1 * 4;
+        Self {
+            width: bounds.1,
+            bits: vec![0; n_states.div_ceil(64)],
+            touched: vec![],
+        }
+    }
+
+    fn clear(&mut self) {
+        for word in self.touched.drain(..) {
+            self.bits[word] = 0;
+        }
+    }
+
+    // Returns whether the state was newly inserted
+    fn insert(&mut self, pos: (usize, usize), direction: Direction) -> bool {
+        let idx = (pos.0 * self.width + pos.1) * 4 + direction as usize;
+        let (word, bit) = (idx / 64, 1 << (idx % 64));
+        if self.bits[word] & bit != 0 {
+            return false;
+        }
+        if self.bits[word] == 0 {
+            self.touched.push(word);
+        }
+        self.bits[word] |= bit;
+        true
+    }
+}
+
+// Every direction the guard has faced in each cell, as a bitmask indexed by Direction
+struct Trail {
+    start: (usize, usize),
+    headings: Vec<Vec<u8>>,
+}
+
+impl Trail {
+    fn init(walker: &Walker) -> Self {
+        let (height, width) = walker.bounds();
+        let mut trail = Self {
+            start: walker.position,
+            headings: vec![vec![0; width]; height],
         };
+        trail.record(walker);
+        trail
+    }
 
-        // Restore walker and grid
-        self.position = init_pos;
-        self.direction = init_dir;
-        self.set_state(&pos_in_front, State::Empty);
+    fn record(&mut self, walker: &Walker) {
+        let (i, j) = walker.position;
+        self.headings[i][j] |= 1 << walker.direction as usize;
+    }
 
-        return found_loop;
+    fn visited(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.headings.iter().enumerate().flat_map(|(i, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|(_j, h)| **h != 0)
+                .map(move |(j, _h)| (i, j))
+        })
+    }
+
+    // Arrow if the guard only went one way, otherwise a line (like the puzzle description)
+    fn symbol(&self, pos: (usize, usize)) -> Option<char> {
+        let headings = self.headings[pos.0][pos.1];
+        let vertical = (1 << Direction::Up as u8) | (1 << Direction::Down as u8);
+        let horizontal = (1 << Direction::Left as u8) | (1 << Direction::Right as u8);
+        if headings == 0 {
+            None
+        } else if headings.count_ones() == 1 {
+            let direction: Direction = (headings.trailing_zeros() as usize).try_into().unwrap();
+            Some(direction.arrow())
+        } else if headings & horizontal == 0 {
+            Some('|')
+        } else if headings & vertical == 0 {
+            Some('-')
+        } else {
+            Some('+')
+        }
     }
 }
 
@@ -169,39 +342,244 @@ fn parse(input: &str) -> Walker {
     Grid(grid).init_walker(pos.unwrap())
 }
 
+// Walks the guard out of the grid, calling on_step after every update
+fn walk(mut walker: Walker, mut on_step: impl FnMut(&Walker, &Trail)) -> Trail {
+    let mut trail = Trail::init(&walker);
+    while walker.update() {
+        trail.record(&walker);
+        on_step(&walker, &trail);
+    }
+    trail
+}
+
+// Every obstruction which would trap the guard in a loop,
+// in the order they are found, along with the step at which the guard finds them
+fn find_looping_inserts(mut walker: Walker) -> Vec<(usize, (usize, usize))> {
+    let jumps = JumpTable::build(&walker.grid);
+    let mut turns = TurnSet::init(walker.bounds());
+    let mut looping_inserts = vec![];
+    let mut seen_inserts = HashSet::new();
+    let mut seen_pos = HashSet::new();
+    let mut step = 0;
+    loop {
+        seen_pos.insert(walker.position);
+        if let Some(pos) = walker.get_pos_in_front() {
+            // Guard to prevent inserting along the path
+            // Because then we would have to rewrite history
+            let is_empty = matches!(walker.get_state(&pos), State::Empty);
+            if is_empty && !seen_pos.contains(&pos) {
+                let has_loop =
+                    jumps.test_for_loop(walker.position, walker.direction, pos, &mut turns);
+                if has_loop && seen_inserts.insert(pos) {
+                    looping_inserts.push((step, pos));
+                }
+            }
+        }
+        if !walker.update() {
+            break;
+        }
+        step += 1;
+    }
+    looping_inserts
+}
+
+fn render(
+    walker: &Walker,
+    trail: &Trail,
+    obstructions: &HashSet<(usize, usize)>,
+    guard: Option<&Walker>,
+) -> String {
+    let mut output = String::new();
+    for (i, row) in walker.grid.0.iter().enumerate() {
+        for (j, state) in row.iter().enumerate() {
+            let pos = (i, j);
+            let cell: ColoredString = if guard.is_some_and(|g| g.position == pos) {
+                let arrow = guard.unwrap().direction.arrow();
+                arrow.to_string().bold().yellow()
+            } else if pos == trail.start {
+                "^".bold().green()
+            } else if obstructions.contains(&pos) {
+                "O".bold().red()
+            } else if let Some(symbol) = trail.symbol(pos) {
+                symbol.to_string().cyan()
+            } else if matches!(state, State::Occupado) {
+                "#".white()
+            } else {
+                ".".dimmed()
+            };
+            output.push_str(&cell.to_string());
+        }
+        output.push('\n');
+    }
+    output
+}
+
+fn write_ppm(
+    path: &str,
+    walker: &Walker,
+    trail: &Trail,
+    obstructions: &HashSet<(usize, usize)>,
+    guard: &Walker,
+    scale: usize,
+) -> std::io::Result<()> {
+    let (height, width) = walker.bounds();
+    let mut pixels = Vec::with_capacity(height * width * scale * scale * 3);
+    for i in 0..height * scale {
+        for j in 0..width * scale {
+            let pos = (i / scale, j / scale);
+            let colour: [u8; 3] = if pos == guard.position {
+                [255, 215, 0]
+            } else if pos == trail.start {
+                [0, 200, 0]
+            } else if obstructions.contains(&pos) {
+                [220, 0, 0]
+            } else if trail.symbol(pos).is_some() {
+                [0, 140, 220]
+            } else if matches!(walker.get_state(&pos), State::Occupado) {
+                [200, 200, 200]
+            } else {
+                [20, 20, 20]
+            };
+            pixels.extend_from_slice(&colour);
+        }
+    }
+    let mut file = fs::File::create(path)?;
+    write!(file, "P6\n{} {}\n255\n", width * scale, height * scale)?;
+    file.write_all(&pixels)
+}
+
+// Obstructions found to cause a loop by the time the guard has taken step steps
+fn obstructions_by(inserts: &[(usize, (usize, usize))], step: usize) -> HashSet<(usize, usize)> {
+    inserts
+        .iter()
+        .filter(|(found_at, _pos)| *found_at <= step)
+        .map(|(_found_at, pos)| *pos)
+        .collect()
+}
+
+fn parse_arg<T: std::str::FromStr>(
+    args: &[String],
+    idx: usize,
+    default: T,
+) -> Result<T, ToolError> {
+    match args.get(idx) {
+        Some(arg) => arg
+            .parse()
+            .map_err(|_e| ToolError::BadArgs(format!("Could not parse \"{arg}\""))),
+        None => Ok(default),
+    }
+}
+
 impl ProblemSolution for Solution {
     fn solve_a(&self, input: &str) -> Option<String> {
-        let mut walker = parse(input);
-        let mut known_pos = HashSet::new();
-        known_pos.insert(walker.position);
-        while walker.update() {
-            known_pos.insert(walker.position);
-        }
-        let answer = known_pos.len();
+        let walker = parse(input);
+        let trail = walk(walker, |_, _| {});
+        let answer = trail.visited().count();
         Some(answer.to_string())
     }
 
     fn solve_b(&self, input: &str) -> Option<String> {
-        let mut walker = parse(input);
-        let mut looping_inserts = HashSet::new();
-        let mut seen_pos = HashSet::new();
-        loop {
-            seen_pos.insert(walker.position);
-            if let Some(pos) = walker.get_pos_in_front() {
-                // Guard to prevent inserting along the path
-                // Because then we would have to rewrite history
-                if !seen_pos.contains(&pos) {
-                    let has_loop = walker.test_for_loop();
-                    if has_loop {
-                        looping_inserts.insert(pos.clone());
+        let walker = parse(input);
+        let n_loops = find_looping_inserts(walker).len();
+        Some(n_loops.to_string())
+    }
+
+    // render : draw the guard's path and every obstruction that would cause a loop
+    // replay [delay_ms] : animate the walk in the terminal
+    // frames <dir> [every] [scale] : write every nth step of the walk as PPM images
+    fn run_tool(&self, input: &str, args: &[String]) -> Result<String, ToolError> {
+        let walker = parse(input);
+        match args[0].as_str() {
+            "render" => {
+                let inserts = find_looping_inserts(parse(input));
+                let trail = walk(parse(input), |_, _| {});
+                let obstructions = obstructions_by(&inserts, usize::MAX);
+                Ok(render(&walker, &trail, &obstructions, None))
+            }
+            "replay" => {
+                let delay = Duration::from_millis(parse_arg(args, 1, 50)?);
+                let inserts = find_looping_inserts(parse(input));
+                let mut step = 0;
+                walk(parse(input), |guard, trail| {
+                    step += 1;
+                    let obstructions = obstructions_by(&inserts, step);
+                    let frame = render(&walker, trail, &obstructions, Some(guard));
+                    // Clear the screen and move the cursor home before each frame
+                    print!("\x1b[2J\x1b[H{frame}");
+                    thread::sleep(delay);
+                });
+                Ok(format!("Replayed {step} steps"))
+            }
+            "frames" => {
+                let Some(dir) = args.get(1) else {
+                    return Err(ToolError::BadArgs("Missing output directory".to_string()));
+                };
+                let every: usize = parse_arg(args, 2, 1)?.max(1);
+                let scale = parse_arg(args, 3, 4)?;
+                fs::create_dir_all(dir).map_err(|e| ToolError::Io(e.to_string()))?;
+                let inserts = find_looping_inserts(parse(input));
+                let mut step = 0;
+                let mut n_frames = 0;
+                let mut result = Ok(());
+                walk(parse(input), |guard, trail| {
+                    step += 1;
+                    if step % every != 0 || result.is_err() {
+                        return;
                     }
-                }
+                    let path = format!("{dir}/frame_{n_frames:05}.ppm");
+                    let obstructions = obstructions_by(&inserts, step);
+                    result = write_ppm(&path, &walker, trail, &obstructions, guard, scale);
+                    n_frames += 1;
+                });
+                result.map_err(|e| ToolError::Io(e.to_string()))?;
+                Ok(format!("Wrote {n_frames} frames to {dir}"))
+            }
+            _ => Err(ToolError::Unknown),
+        }
+    }
+
+    // A size x size lab with one square in ten obstructed, retried until the guard walks out
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        loop {
+            let mut grid: Vec<Vec<State>> = (0..size)
+                .map(|_| {
+                    (0..size)
+                        .map(|_| {
+                            if rng.one_in(10) {
+                                State::Occupado
+                            } else {
+                                State::Empty
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            let start = (rng.range(0..=size - 1), rng.range(0..=size - 1));
+            grid[start.0][start.1] = State::Empty;
+            let grid = Grid(grid);
+            let jumps = JumpTable::build(&grid);
+            // An insert off the grid never blocks anything
+            let off_grid = (size, size);
+            if jumps.test_for_loop(
+                start,
+                Direction::Up,
+                off_grid,
+                &mut TurnSet::init((size, size)),
+            ) {
+                continue;
             }
-            if !walker.update() {
-                break;
+            let mut output = String::new();
+            for (i, row) in grid.0.iter().enumerate() {
+                for (j, state) in row.iter().enumerate() {
+                    output.push(match state {
+                        _ if (i, j) == start => '^',
+                        State::Empty => '.',
+                        State::Occupado => '#',
+                    });
+                }
+                output.push('\n');
             }
+            return Some(output);
         }
-        let n_loops = looping_inserts.len();
-        Some(n_loops.to_string())
     }
 }
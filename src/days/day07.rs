@@ -1,8 +1,64 @@
+use crate::problem::ToolError;
+use crate::rng::Rng;
 use crate::ProblemSolution;
 #[allow(unused_imports)]
 use aoc_parse::{parser, prelude::*};
 pub struct Solution {}
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Add,
+    Multiply,
+    Concat,
+}
+
+const PART_A_OPERATORS: &[Operator] = &[Operator::Add, Operator::Multiply];
+const PART_B_OPERATORS: &[Operator] = &[Operator::Add, Operator::Multiply, Operator::Concat];
+
+impl Operator {
+    fn symbol(self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Multiply => "*",
+            Operator::Concat => "||",
+        }
+    }
+
+    // Parses a set of operators such as "+*|". Repeats are dropped, or solutions using
+    // them would be counted more than once
+    fn parse_set(s: &str) -> Option<Vec<Self>> {
+        let mut set = vec![];
+        for c in s.chars() {
+            let op = match c {
+                '+' => Operator::Add,
+                '*' => Operator::Multiply,
+                '|' => Operator::Concat,
+                _ => return None,
+            };
+            if !set.contains(&op) {
+                set.push(op);
+            }
+        }
+        Some(set)
+    }
+
+    // The value the left-hand side must have for `left op right == target`, if there is one.
+    // Puzzle numbers are all positive, so there's at most one and the left-hand side is
+    // never bigger than the target
+    fn undo(self, target: usize, right: usize) -> Option<usize> {
+        match self {
+            Operator::Add => target.checked_sub(right),
+            Operator::Multiply => {
+                (right != 0 && target.is_multiple_of(right)).then(|| target / right)
+            }
+            Operator::Concat => {
+                let power_of_10 = 10usize.pow(right.max(1).ilog10() + 1);
+                (target % power_of_10 == right).then_some(target / power_of_10)
+            }
+        }
+    }
+}
+
 struct Equation {
     target: usize,
     numbers: Vec<usize>,
@@ -12,68 +68,87 @@ impl Equation {
     fn start_work(&self) -> WorkingEquation<'_> {
         WorkingEquation {
             equation: self,
-            left_evaluated: self.numbers[0],
-            remaining_idx: 1,
+            remaining_target: self.target,
+            remaining_len: self.numbers.len(),
+        }
+    }
+
+    // Operators to put between the numbers, left to right, to reach the target
+    fn solution(&self, operators: &[Operator]) -> Option<Vec<Operator>> {
+        let mut chosen = vec![];
+        if self.start_work().find_solution(operators, &mut chosen) {
+            chosen.reverse();
+            Some(chosen)
+        } else {
+            None
         }
     }
 
-    fn has_solution<const ALLOW_CONCAT: bool>(&self) -> bool {
-        self.start_work().yields_solution::<ALLOW_CONCAT>()
+    fn has_solution(&self, operators: &[Operator]) -> bool {
+        self.start_work().find_solution(operators, &mut vec![])
+    }
+
+    fn count_solutions(&self, operators: &[Operator]) -> usize {
+        self.start_work().count_solutions(operators)
+    }
+
+    fn display_solution(&self, solution: &[Operator]) -> String {
+        let mut output = format!("{} = {}", self.target, self.numbers[0]);
+        for (op, number) in solution.iter().zip(&self.numbers[1..]) {
+            output.push_str(&format!(" {} {number}", op.symbol()));
+        }
+        output
     }
 }
 
+// Works from the right, undoing the last operator, which lets us prune anything
+// that doesn't subtract, divide or strip a suffix cleanly
 struct WorkingEquation<'a> {
     equation: &'a Equation,
-    left_evaluated: usize,
-    remaining_idx: usize,
-}
-
-// From https://www.reddit.com/r/rust/comments/191l3ot/concatinate_two_numbers/
-fn concat(a: usize, b: usize) -> usize {
-    a as usize * 10usize.pow(b.ilog10() + 1) + b as usize
+    remaining_target: usize,
+    remaining_len: usize,
 }
 
-impl<'a> WorkingEquation<'a> {
-    fn add_attempt(&self) -> Self {
-        WorkingEquation {
+impl WorkingEquation<'_> {
+    fn attempt(&self, op: Operator) -> Option<Self> {
+        let right = self.equation.numbers[self.remaining_len - 1];
+        Some(WorkingEquation {
             equation: self.equation,
-            left_evaluated: self.left_evaluated + self.equation.numbers[self.remaining_idx],
-            remaining_idx: self.remaining_idx + 1,
-        }
+            remaining_target: op.undo(self.remaining_target, right)?,
+            remaining_len: self.remaining_len - 1,
+        })
     }
 
-    fn mult_attempt(&self) -> Self {
-        WorkingEquation {
-            equation: self.equation,
-            left_evaluated: self.left_evaluated * self.equation.numbers[self.remaining_idx],
-            remaining_idx: self.remaining_idx + 1,
-        }
+    fn is_solved(&self) -> bool {
+        self.equation.numbers[0] == self.remaining_target
     }
 
-    fn concat_attempt(&self) -> Self {
-        WorkingEquation {
-            equation: self.equation,
-            left_evaluated: concat(
-                self.left_evaluated,
-                self.equation.numbers[self.remaining_idx],
-            ),
-            remaining_idx: self.remaining_idx + 1,
+    // Pushes the operators used, right to left
+    fn find_solution(&self, operators: &[Operator], chosen: &mut Vec<Operator>) -> bool {
+        if self.remaining_len == 1 {
+            return self.is_solved();
         }
+        for op in operators {
+            if let Some(next) = self.attempt(*op) {
+                chosen.push(*op);
+                if next.find_solution(operators, chosen) {
+                    return true;
+                }
+                chosen.pop();
+            }
+        }
+        false
     }
 
-    fn yields_solution<const ALLOW_CONCAT: bool>(&self) -> bool {
-        // Used all the numbers, did we get the right answer?
-        if self.remaining_idx == self.equation.numbers.len() {
-            return self.left_evaluated == self.equation.target;
-        }
-        // We can only increase so short-circuit failure
-        if self.left_evaluated > self.equation.target {
-            return false;
+    fn count_solutions(&self, operators: &[Operator]) -> usize {
+        if self.remaining_len == 1 {
+            return self.is_solved() as usize;
         }
-
-        self.add_attempt().yields_solution::<ALLOW_CONCAT>()
-            || self.mult_attempt().yields_solution::<ALLOW_CONCAT>()
-            || (ALLOW_CONCAT && self.concat_attempt().yields_solution::<ALLOW_CONCAT>())
+        operators
+            .iter()
+            .filter_map(|op| self.attempt(*op))
+            .map(|next| next.count_solutions(operators))
+            .sum()
     }
 }
 
@@ -90,7 +165,7 @@ impl ProblemSolution for Solution {
         let equations = parse(input);
         let answer: usize = equations
             .into_iter()
-            .filter(|eq| eq.has_solution::<false>())
+            .filter(|eq| eq.has_solution(PART_A_OPERATORS))
             .map(|eq| eq.target)
             .sum();
         Some(answer.to_string())
@@ -100,9 +175,70 @@ impl ProblemSolution for Solution {
         let equations = parse(input);
         let answer: usize = equations
             .into_iter()
-            .filter(|eq| eq.has_solution::<true>())
+            .filter(|eq| eq.has_solution(PART_B_OPERATORS))
             .map(|eq| eq.target)
             .sum();
         Some(answer.to_string())
     }
+
+    // explain [operators] [count] : a solution for every equation using the given operators
+    // (e.g. "+*|", the default, where | is concatenation), optionally counting all solutions
+    fn run_tool(&self, input: &str, args: &[String]) -> Result<String, ToolError> {
+        if args[0] != "explain" {
+            return Err(ToolError::Unknown);
+        }
+        let operators = match args.get(1) {
+            Some(arg) => Operator::parse_set(arg).ok_or_else(|| {
+                ToolError::BadArgs(format!("Could not parse operators \"{arg}\""))
+            })?,
+            None => PART_B_OPERATORS.to_vec(),
+        };
+        let count = match args.get(2).map(|arg| arg.as_str()) {
+            Some("count") => true,
+            Some(arg) => return Err(ToolError::BadArgs(format!("Unexpected \"{arg}\""))),
+            None => false,
+        };
+        let mut output = String::new();
+        for equation in parse(input) {
+            match equation.solution(&operators) {
+                Some(solution) => output.push_str(&equation.display_solution(&solution)),
+                None => output.push_str(&format!("{}: no solution", equation.target)),
+            }
+            if count {
+                let n_solutions = equation.count_solutions(&operators);
+                let plural = if n_solutions == 1 { "" } else { "s" };
+                output.push_str(&format!(" ({n_solutions} solution{plural})"));
+            }
+            output.push('\n');
+        }
+        Ok(output)
+    }
+
+    // size equations of 2 to 12 numbers. Most targets are built from random operators,
+    // the rest nudged so they're probably unreachable. Targets are kept below 10^14
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        let mut output = String::new();
+        for _ in 0..size {
+            let mut numbers = vec![rng.range(1..=99)];
+            let mut target = numbers[0];
+            for _ in 1..rng.range(2..=12) {
+                let number = rng.range(1..=99);
+                let next = match rng.range(0..=2) {
+                    0 => Some(target + number),
+                    1 => target.checked_mul(number),
+                    _ => format!("{target}{number}").parse().ok(),
+                };
+                target = next
+                    .filter(|next| *next < 10usize.pow(14))
+                    .unwrap_or(target + number);
+                numbers.push(number);
+            }
+            if rng.one_in(3) {
+                target += rng.range(1..=9);
+            }
+            let numbers: Vec<String> = numbers.iter().map(|n| n.to_string()).collect();
+            output.push_str(&format!("{target}: {}\n", numbers.join(" ")));
+        }
+        Some(output)
+    }
 }
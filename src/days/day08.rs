@@ -1,93 +1,214 @@
-use std::{
-    collections::{HashMap, HashSet},
-    iter,
-};
+use std::collections::{HashMap, HashSet};
 
+use crate::problem::ToolError;
+use crate::rng::Rng;
 use crate::ProblemSolution;
 #[allow(unused_imports)]
 use aoc_parse::{parser, prelude::*};
+use colored::Colorize;
 pub struct Solution {}
 
-struct GridSummary {
-    ants: HashMap<char, Vec<(usize, usize)>>,
-    size: (usize, usize),
+// A multiple of the step, as a fraction with a positive denominator
+#[derive(Clone, Copy)]
+struct Ratio {
+    num: isize,
+    den: isize,
 }
 
-impl GridSummary {
-    fn in_grid(&self, pos: &(isize, isize)) -> bool {
-        pos.0 >= 0 && pos.1 >= 0 && (pos.0 as usize) < self.size.0 && (pos.1 as usize) < self.size.1
+impl Ratio {
+    fn whole(num: isize) -> Self {
+        Self { num, den: 1 }
     }
 
-    fn get_half_line(
-        &self,
-        base: (usize, usize),
-        delta: (isize, isize),
-    ) -> impl Iterator<Item = (isize, isize)> {
-        let base = (base.0 as isize, base.1 as isize);
-        (0..).map(move |i| (base.0 + i * delta.0, base.1 + i * delta.1))
+    // "2", "-1" or "1/3"
+    fn parse(s: &str) -> Option<Self> {
+        let (num, den) = s.split_once('/').unwrap_or((s, "1"));
+        let (num, den): (isize, isize) = (num.parse().ok()?, den.parse().ok()?);
+        (den != 0).then_some(Self {
+            num: num * den.signum(),
+            den: den.abs(),
+        })
     }
+}
 
-    // TODO: Shouldn't need a Box here
-    fn antinodes_for_pair<'a, const PART_A: bool>(
-        &'a self,
-        a: (usize, usize),
-        b: (usize, usize),
-    ) -> Box<dyn Iterator<Item = (usize, usize)> + 'a> {
-        if PART_A {
-            let delta = (b.0 as isize - a.0 as isize, b.1 as isize - a.1 as isize);
-            let n1 = (a.0 as isize - delta.0, a.1 as isize - delta.1);
-            let n2 = (b.0 as isize + delta.0, b.1 as isize + delta.1);
-            let n1 = iter::once(n1);
-            let n2 = iter::once(n2);
-            Box::new(
-                n1.chain(n2)
-                    .filter(|pos| self.in_grid(pos))
-                    .map(|(p0, p1)| (p0 as usize, p1 as usize)),
-            )
-        } else {
-            let delta = (b.0 as isize - a.0 as isize, b.1 as isize - a.1 as isize);
-            let b_half_line = self
-                .get_half_line(b, delta)
-                .take_while(|pos| self.in_grid(pos));
-            let a_half_line = self
-                .get_half_line(a, (-delta.0, -delta.1))
-                .take_while(|pos| self.in_grid(pos));
-            Box::new(
-                b_half_line
-                    .chain(a_half_line)
-                    .map(|(p0, p1)| (p0 as usize, p1 as usize)),
-            )
+// Which values of k count, where the antinodes of a pair a, b are at a + k * step
+enum Multiples {
+    // Fractional multiples only count where they land exactly on a grid point
+    Listed(Vec<Ratio>),
+    // From a to b inclusive
+    Between,
+    // Every k landing inside the grid
+    All,
+}
+
+// The step is b - a, or when reduced, b - a divided by the gcd of its coordinates
+// so that every grid point on the line is a multiple
+struct AntinodeRule {
+    reduced: bool,
+    multiples: Multiples,
+}
+
+impl AntinodeRule {
+    fn part_a() -> Self {
+        Self {
+            reduced: false,
+            multiples: Multiples::Listed(vec![Ratio::whole(-1), Ratio::whole(2)]),
+        }
+    }
+
+    fn part_b() -> Self {
+        Self {
+            reduced: false,
+            multiples: Multiples::All,
         }
     }
 
-    fn antinodes_for_positions<'a, const PART_A: bool>(
+    // "a", "b", "lattice" (every grid point on the line), "between" (grid points from
+    // one antenna to the other) or a list of multiples of the delta such as "-1,2" or "1/3,2/3"
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "a" => Some(Self::part_a()),
+            "b" => Some(Self::part_b()),
+            "lattice" => Some(Self {
+                reduced: true,
+                multiples: Multiples::All,
+            }),
+            "between" => Some(Self {
+                reduced: true,
+                multiples: Multiples::Between,
+            }),
+            _ => {
+                let multiples = s.split(',').map(Ratio::parse).collect::<Option<_>>()?;
+                Some(Self {
+                    reduced: false,
+                    multiples: Multiples::Listed(multiples),
+                })
+            }
+        }
+    }
+}
+
+fn gcd(a: isize, b: isize) -> isize {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+// Antennas in a D dimensional box, though puzzle inputs are always 2D
+struct GridSummary<const D: usize> {
+    ants: HashMap<char, Vec<[usize; D]>>,
+    size: [usize; D],
+}
+
+impl<const D: usize> GridSummary<D> {
+    fn in_grid(&self, pos: &[isize; D]) -> bool {
+        pos.iter()
+            .zip(self.size)
+            .all(|(p, size)| *p >= 0 && (*p as usize) < size)
+    }
+
+    fn antinodes_for_pair(
+        &self,
+        rule: &AntinodeRule,
+        a: [usize; D],
+        b: [usize; D],
+    ) -> Vec<[usize; D]> {
+        let delta: [isize; D] = std::array::from_fn(|d| b[d] as isize - a[d] as isize);
+        let n_steps = if rule.reduced {
+            delta.iter().fold(0, |g, x| gcd(g, *x))
+        } else {
+            1
+        };
+        let step = delta.map(|x| x / n_steps);
+        // None if a fractional multiple falls between grid points
+        let at = |k: Ratio| -> Option<[isize; D]> {
+            let mut pos = [0; D];
+            for d in 0..D {
+                let offset = k.num * step[d];
+                if offset % k.den != 0 {
+                    return None;
+                }
+                pos[d] = a[d] as isize + offset / k.den;
+            }
+            Some(pos)
+        };
+        let inside = |k: isize| at(Ratio::whole(k)).is_some_and(|pos| self.in_grid(&pos));
+        let ks: Vec<Ratio> = match &rule.multiples {
+            Multiples::Listed(ks) => ks.clone(),
+            Multiples::Between => (0..=n_steps).map(Ratio::whole).collect(),
+            // The grid is convex, so the multiples inside it are contiguous and include a
+            Multiples::All => {
+                let forwards = (0..).take_while(|k| inside(*k));
+                let backwards = (1..).map(|k: isize| -k).take_while(|k| inside(*k));
+                forwards.chain(backwards).map(Ratio::whole).collect()
+            }
+        };
+        ks.into_iter()
+            .filter_map(at)
+            .filter(|pos| self.in_grid(pos))
+            .map(|pos| pos.map(|p| p as usize))
+            .collect()
+    }
+
+    fn antinodes_for_positions<'a>(
         &'a self,
-        positions: &'a Vec<(usize, usize)>,
-    ) -> impl Iterator<Item = (usize, usize)> + 'a {
+        rule: &'a AntinodeRule,
+        positions: &'a [[usize; D]],
+    ) -> impl Iterator<Item = [usize; D]> + 'a {
         let n = positions.len();
         let idxs = (0..n).flat_map(move |i| ((i + 1)..n).map(move |j| (i, j)));
-        idxs.flat_map(move |(i, j)| self.antinodes_for_pair::<PART_A>(positions[i], positions[j]))
+        idxs.flat_map(move |(i, j)| self.antinodes_for_pair(rule, positions[i], positions[j]))
     }
 
-    fn get_antinodes<'a, const PART_A: bool>(
+    fn get_antinodes<'a>(
         &'a self,
-    ) -> impl Iterator<Item = (usize, usize)> + 'a {
+        rule: &'a AntinodeRule,
+    ) -> impl Iterator<Item = [usize; D]> + 'a {
         self.ants
             .values()
-            .flat_map(|positions| self.antinodes_for_positions::<PART_A>(positions))
+            .flat_map(|positions| self.antinodes_for_positions(rule, positions))
+    }
+}
+
+impl GridSummary<2> {
+    // The grid with one frequency's antennas highlighted and its antinodes marked #
+    fn render_frequency(&self, rule: &AntinodeRule, freq: char) -> String {
+        let positions = &self.ants[&freq];
+        let antinodes: HashSet<_> = self.antinodes_for_positions(rule, positions).collect();
+        let antennas: HashMap<_, _> = self
+            .ants
+            .iter()
+            .flat_map(|(f, positions)| positions.iter().map(|pos| (*pos, *f)))
+            .collect();
+        let mut output = format!("Frequency {freq}: {} antinodes\n", antinodes.len());
+        for i in 0..self.size[0] {
+            for j in 0..self.size[1] {
+                let cell = match antennas.get(&[i, j]) {
+                    Some(f) if *f == freq => f.to_string().bold().cyan(),
+                    _ if antinodes.contains(&[i, j]) => "#".bold().yellow(),
+                    Some(f) => f.to_string().dimmed(),
+                    None => ".".dimmed(),
+                };
+                output.push_str(&cell.to_string());
+            }
+            output.push('\n');
+        }
+        output
     }
 }
 
-fn parse(input: &str) -> GridSummary {
-    let mut ants: HashMap<char, Vec<(usize, usize)>> = HashMap::new();
+fn parse(input: &str) -> GridSummary<2> {
+    let mut ants: HashMap<char, Vec<[usize; 2]>> = HashMap::new();
     let lines: Vec<_> = input.lines().collect();
-    let size = (lines.len(), lines.first().unwrap().len());
+    let size = [lines.len(), lines.first().unwrap().len()];
     for (i, line) in lines.iter().enumerate() {
         for (j, c) in line.chars().enumerate() {
             if c == '.' {
                 continue;
             }
-            ants.entry(c).or_default().push((i, j));
+            ants.entry(c).or_default().push([i, j]);
         }
     }
     GridSummary { ants, size }
@@ -96,15 +217,87 @@ fn parse(input: &str) -> GridSummary {
 impl ProblemSolution for Solution {
     fn solve_a(&self, input: &str) -> Option<String> {
         let grid_summary = parse(input);
-        let antinodes: HashSet<_> = grid_summary.get_antinodes::<true>().collect();
+        let rule = AntinodeRule::part_a();
+        let antinodes: HashSet<_> = grid_summary.get_antinodes(&rule).collect();
         let answer = antinodes.len();
         Some(answer.to_string())
     }
 
     fn solve_b(&self, input: &str) -> Option<String> {
         let grid_summary = parse(input);
-        let antinodes: HashSet<_> = grid_summary.get_antinodes::<false>().collect();
+        let rule = AntinodeRule::part_b();
+        let antinodes: HashSet<_> = grid_summary.get_antinodes(&rule).collect();
         let answer = antinodes.len();
         Some(answer.to_string())
     }
+
+    // render [rule] [frequency] : the grid with each frequency's antinodes overlaid, where rule is
+    // a, b (the default), lattice, between or a list of multiples of the delta such as -1,2 or 1/3
+    fn run_tool(&self, input: &str, args: &[String]) -> Result<String, ToolError> {
+        if args[0] != "render" {
+            return Err(ToolError::Unknown);
+        }
+        let rule = match args.get(1) {
+            Some(arg) => AntinodeRule::parse(arg)
+                .ok_or_else(|| ToolError::BadArgs(format!("Could not parse rule \"{arg}\"")))?,
+            None => AntinodeRule::part_b(),
+        };
+        let grid_summary = parse(input);
+        let mut frequencies: Vec<char> = grid_summary.ants.keys().copied().collect();
+        frequencies.sort();
+        if let Some(arg) = args.get(2) {
+            let freq = arg.chars().next().unwrap_or_default();
+            if !grid_summary.ants.contains_key(&freq) {
+                return Err(ToolError::BadArgs(format!(
+                    "No antennas with frequency \"{arg}\""
+                )));
+            }
+            frequencies = vec![freq];
+        }
+        let antinodes: HashSet<_> = grid_summary.get_antinodes(&rule).collect();
+        let mut output = String::new();
+        for freq in frequencies {
+            output.push_str(&grid_summary.render_frequency(&rule, freq));
+            output.push('\n');
+        }
+        output.push_str(&format!("{} antinodes in total\n", antinodes.len()));
+        Ok(output)
+    }
+
+    // A size x size grid with up to size / 2 frequencies of four antennas each
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        let frequencies: Vec<char> = ('a'..='z').chain('A'..='Z').chain('0'..='9').collect();
+        let mut grid = vec![vec!['.'; size]; size];
+        for freq in frequencies.iter().take((size / 2).max(1)) {
+            for _ in 0..4 {
+                let (i, j) = (rng.range(0..=size - 1), rng.range(0..=size - 1));
+                grid[i][j] = *freq;
+            }
+        }
+        let rows: Vec<String> = grid
+            .into_iter()
+            .map(|row| row.into_iter().chain(['\n']).collect())
+            .collect();
+        Some(rows.concat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn antinodes_in_three_dimensions() {
+        let grid = GridSummary {
+            ants: HashMap::from([('a', vec![[3, 3, 3], [6, 6, 6]])]),
+            size: [10, 10, 10],
+        };
+        let antinodes: HashSet<_> = grid.get_antinodes(&AntinodeRule::part_a()).collect();
+        assert_eq!(antinodes, HashSet::from([[0, 0, 0], [9, 9, 9]]));
+        let between = AntinodeRule::parse("between").unwrap();
+        assert_eq!(grid.get_antinodes(&between).count(), 4);
+        let thirds = AntinodeRule::parse("1/3,1/2").unwrap();
+        let antinodes: Vec<_> = grid.get_antinodes(&thirds).collect();
+        assert_eq!(antinodes, [[4, 4, 4]]);
+    }
 }
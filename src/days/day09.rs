@@ -1,8 +1,13 @@
+use crate::problem::ToolError;
+use crate::rng::Rng;
 use crate::ProblemSolution;
 #[allow(unused_imports)]
 use aoc_parse::{parser, prelude::*};
 use itertools::Itertools;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::iter;
+use std::time::Instant;
 pub struct Solution {}
 
 #[derive(Clone, Copy)]
@@ -18,68 +23,107 @@ impl BlockDescriptor {
             BlockDescriptor::Empty(len) => *len,
         }
     }
+}
 
-    fn id(&self) -> Option<usize> {
-        match self {
-            BlockDescriptor::File(id, _len) => Some(*id),
-            BlockDescriptor::Empty(_len) => None,
-        }
+struct FileSpan {
+    id: usize,
+    start: usize,
+    len: usize,
+}
+
+impl FileSpan {
+    // Sum of id * position over start..start + len
+    fn checksum(&self) -> usize {
+        self.id * (self.len * self.start + self.len * self.len.saturating_sub(1) / 2)
     }
 }
 
-fn get_checksum(blocks: &Vec<BlockDescriptor>) -> usize {
-    let counts = blocks.iter().map(|bl| bl.len());
-    let ids = blocks.iter().map(|bl| bl.id());
-    let disk_map = ids
-        .zip(counts)
-        .flat_map(|(id, count)| iter::repeat(id).take(count));
-    disk_map
-        .enumerate()
-        .filter_map(|(idx, val)| val.map(|v| v * idx))
-        .sum()
+// Digits are at most 9 so there are only 9 possible sizes of free span
+const MAX_SPAN: usize = 9;
+
+struct Disk {
+    // Indexed by id
+    files: Vec<FileSpan>,
+    // free[len] holds the starts of every free span of exactly len blocks
+    free: [BinaryHeap<Reverse<usize>>; MAX_SPAN + 1],
+    size: usize,
 }
 
-fn execute_defrag(descriptors: &mut Vec<BlockDescriptor>, defrag: (usize, usize)) {
-    let len_empty = descriptors[defrag.0].len();
-    let len_file = descriptors[defrag.1].len();
-    if len_empty == len_file {
-        descriptors.swap(defrag.0, defrag.1);
-        return;
+impl Disk {
+    fn from_descriptors(descriptors: impl Iterator<Item = BlockDescriptor>) -> Self {
+        let mut files = vec![];
+        let mut free: [BinaryHeap<Reverse<usize>>; MAX_SPAN + 1] = Default::default();
+        let mut position = 0;
+        for descriptor in descriptors {
+            match descriptor {
+                BlockDescriptor::File(id, len) => files.push(FileSpan {
+                    id,
+                    start: position,
+                    len,
+                }),
+                BlockDescriptor::Empty(len) => {
+                    if len > 0 {
+                        free[len].push(Reverse(position));
+                    }
+                }
+            }
+            position += descriptor.len();
+        }
+        Self {
+            files,
+            free,
+            size: position,
+        }
+    }
+
+    // Leftmost free span that can hold len blocks, as (span length, start)
+    fn find_free_span(&self, len: usize) -> Option<(usize, usize)> {
+        (len..=MAX_SPAN)
+            .filter_map(|span_len| Some((span_len, self.free[span_len].peek()?.0)))
+            .min_by_key(|(_span_len, start)| *start)
     }
-    let new_empty_0 = BlockDescriptor::Empty(len_file);
-    let new_empty_1 = BlockDescriptor::Empty(len_empty - len_file);
-    descriptors[defrag.0] = new_empty_0;
-    descriptors.insert(defrag.0 + 1, new_empty_1);
-    descriptors.swap(defrag.0, defrag.1 + 1);
-}
 
-fn find_defrag_opportunity(descriptors: &Vec<BlockDescriptor>) -> Option<(usize, usize)> {
-    let mut i = 0;
-    let mut j = descriptors.len() - 1;
-    loop {
-        if j == 0 {
-            // Attempted to move all file blocks
-            return None;
+    // Try to move file id to the leftmost free span that fits it
+    // Space freed up behind the file is never needed again, because files only move left
+    // and every file left to move lies to the left of it
+    fn try_move_file(&mut self, id: usize) -> bool {
+        let file = &self.files[id];
+        let Some((span_len, start)) = self.find_free_span(file.len) else {
+            return false;
+        };
+        if start >= file.start {
+            return false;
         }
-        if i >= j {
-            // Moved i forward far enough, reset and try to move next file
-            i = 0;
-            j -= 1;
+        self.free[span_len].pop();
+        let remaining = span_len - file.len;
+        if remaining > 0 {
+            self.free[remaining].push(Reverse(start + file.len));
         }
-        match (&descriptors[i], &descriptors[j]) {
-            (BlockDescriptor::File(_, _), _) => {
-                i += 1;
-            }
-            (_, BlockDescriptor::Empty(_)) => j -= 1,
-            (BlockDescriptor::Empty(len_empty), BlockDescriptor::File(_id, len_file)) => {
-                if len_empty < len_file {
-                    i += 1;
-                } else {
-                    return Some((i, j));
-                }
+        self.files[id].start = start;
+        true
+    }
+
+    fn defrag(&mut self, mut on_move: impl FnMut(&Disk)) {
+        for id in (0..self.files.len()).rev() {
+            if self.try_move_file(id) {
+                on_move(self);
             }
         }
     }
+
+    fn checksum(&self) -> usize {
+        self.files.iter().map(|f| f.checksum()).sum()
+    }
+
+    // Like the puzzle description, with ids written in base 36
+    fn layout(&self) -> String {
+        let mut layout = vec!['.'; self.size];
+        for file in self.files.iter() {
+            let symbol = char::from_digit((file.id % 36) as u32, 36).unwrap();
+            layout[file.start..file.start + file.len].fill(symbol);
+        }
+        layout.into_iter().collect()
+    }
 }
 
 fn produce_block_descriptors(input: &str) -> impl Iterator<Item = BlockDescriptor> + '_ {
@@ -91,34 +135,73 @@ fn produce_block_descriptors(input: &str) -> impl Iterator<Item = BlockDescripto
     })
 }
 
-fn sort_disk_map(map: &mut Vec<Option<usize>>) {
-    let mut i = 0;
-    let mut j = map.len() - 1;
-    loop {
-        if i >= j {
-            break;
-        }
-        if !map[i].is_none() {
-            i += 1;
-            continue;
-        }
-        if !map[j].is_some() {
-            j -= 1;
-            continue;
+// Two pointers: walk forward through the disk, filling each gap with blocks taken from the
+// back of the last file. Each run of blocks contributes to the checksum arithmetically,
+// so nothing is expanded block by block.
+fn streaming_checksum(descriptors: &[BlockDescriptor]) -> u128 {
+    let run_checksum = |id: usize, start: usize, len: usize| {
+        let (id, start, len) = (id as u128, start as u128, len as u128);
+        id * (len * start + len * len.saturating_sub(1) / 2)
+    };
+    let file_at = |idx: usize| match descriptors[idx] {
+        BlockDescriptor::File(id, len) => (id, len),
+        BlockDescriptor::Empty(_) => panic!("Expected file at descriptor {idx}"),
+    };
+
+    let mut checksum = 0;
+    let mut position = 0;
+    let mut front = 0;
+    // Files sit at even indices
+    let Some(mut back) = (0..descriptors.len()).rev().find(|idx| idx % 2 == 0) else {
+        return 0;
+    };
+    let (mut back_id, mut back_remaining) = file_at(back);
+    while front < back {
+        match descriptors[front] {
+            BlockDescriptor::File(id, len) => {
+                checksum += run_checksum(id, position, len);
+                position += len;
+            }
+            BlockDescriptor::Empty(mut gap) => {
+                while gap > 0 && back > front {
+                    let moved = gap.min(back_remaining);
+                    checksum += run_checksum(back_id, position, moved);
+                    position += moved;
+                    gap -= moved;
+                    back_remaining -= moved;
+                    if back_remaining == 0 {
+                        // Skip over the empty span before the previous file
+                        back -= 2;
+                        (back_id, back_remaining) = file_at(back);
+                    }
+                }
+            }
         }
-        map.swap(i, j);
+        front += 1;
     }
+    // Whatever is left of the last file we were taking from stays where it is
+    if front == back {
+        checksum += run_checksum(back_id, position, back_remaining);
+    }
+    checksum
 }
 
-fn produce_disk_map(input: &str) -> impl Iterator<Item = Option<usize>> + '_ {
-    let counts = parse(input);
-    let ids = produce_identifiers();
-    ids.zip(counts)
-        .flat_map(|(id, count)| iter::repeat(id).take(count))
+// Random disk map in the puzzle format: files of 1-9 blocks, gaps of 0-9 blocks
+fn synthetic_disk_map(n_digits: usize, rng: &mut Rng) -> String {
+    (0..n_digits)
+        .map(|idx| {
+            let len = if idx % 2 == 0 {
+                rng.range(1..=9)
+            } else {
+                rng.range(0..=9)
+            };
+            char::from_digit(len as u32, 10).unwrap()
+        })
+        .collect()
 }
 
 fn produce_identifiers() -> impl Iterator<Item = Option<usize>> {
-    let ids = (0..).map(|i| Some(i));
+    let ids = (0..).map(Some);
     let nones = iter::repeat(None);
     ids.interleave(nones)
 }
@@ -134,22 +217,63 @@ fn parse(input: &str) -> impl Iterator<Item = usize> + '_ {
 
 impl ProblemSolution for Solution {
     fn solve_a(&self, input: &str) -> Option<String> {
-        let mut map: Vec<_> = produce_disk_map(input).collect();
-        sort_disk_map(&mut map);
-        let checksum: usize = map
-            .into_iter()
-            .enumerate()
-            .filter_map(|(idx, val)| val.map(|v| v * idx))
-            .sum();
+        let descriptors: Vec<_> = produce_block_descriptors(input).collect();
+        let checksum = streaming_checksum(&descriptors);
         Some(checksum.to_string())
     }
 
     fn solve_b(&self, input: &str) -> Option<String> {
-        let mut blocks: Vec<_> = produce_block_descriptors(input).collect();
-        while let Some(defrag) = find_defrag_opportunity(&blocks) {
-            execute_defrag(&mut blocks, defrag);
-        }
-        let checksum = get_checksum(&blocks);
+        let mut disk = Disk::from_descriptors(produce_block_descriptors(input));
+        disk.defrag(|_| {});
+        let checksum = disk.checksum();
         Some(checksum.to_string())
     }
+
+    // layout : print the disk after every file move in part B (only sensible for small inputs)
+    // stress <n_digits> [seed] : time part A on a random disk map, ignoring the input
+    fn run_tool(&self, input: &str, args: &[String]) -> Result<String, ToolError> {
+        if args[0] == "stress" {
+            let parse_arg = |idx: usize| -> Result<Option<u64>, ToolError> {
+                args.get(idx)
+                    .map(|arg| {
+                        arg.parse()
+                            .map_err(|_e| ToolError::BadArgs(format!("Could not parse \"{arg}\"")))
+                    })
+                    .transpose()
+            };
+            let Some(n_digits) = parse_arg(1)? else {
+                return Err(ToolError::BadArgs("Missing number of digits".to_string()));
+            };
+            if n_digits == 0 {
+                return Err(ToolError::BadArgs(
+                    "Number of digits must be positive".to_string(),
+                ));
+            }
+            let seed = parse_arg(2)?.unwrap_or(0);
+            let disk_map = synthetic_disk_map(n_digits as usize, &mut Rng::seeded(seed));
+            let timer = Instant::now();
+            let descriptors: Vec<_> = produce_block_descriptors(&disk_map).collect();
+            let checksum = streaming_checksum(&descriptors);
+            let elapsed = timer.elapsed();
+            return Ok(format!(
+                "Checksum of {n_digits} digit disk map (seed {seed}) is {checksum}, took {elapsed:?}"
+            ));
+        }
+        if args[0] != "layout" {
+            return Err(ToolError::Unknown);
+        }
+        let mut disk = Disk::from_descriptors(produce_block_descriptors(input));
+        let mut output = disk.layout();
+        output.push('\n');
+        disk.defrag(|d| {
+            output.push_str(&d.layout());
+            output.push('\n');
+        });
+        Ok(output)
+    }
+
+    // A disk map of size digits
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        Some(synthetic_disk_map(size, rng) + "\n")
+    }
 }
@@ -1,8 +1,11 @@
-use std::{collections::HashSet, iter};
+use std::collections::{HashMap, VecDeque};
 
+use crate::problem::ToolError;
+use crate::rng::Rng;
 use crate::ProblemSolution;
 #[allow(unused_imports)]
 use aoc_parse::{parser, prelude::*};
+use colored::Colorize;
 pub struct Solution {}
 
 type Position = (usize, usize);
@@ -39,33 +42,176 @@ impl Grid {
             .filter_map(move |d| self.map_to_pos((pos.0 + d.0, pos.1 + d.1)))
     }
 
-    fn find_peaks(&self, starting: Position) -> Box<dyn Iterator<Item = Position> + '_> {
-        if self.height(&starting) == 9 {
-            Box::new(iter::once(starting))
-        } else {
-            let start_height = self.height(&starting);
-            Box::new(
-                self.nbrs(starting)
-                    .filter(move |nbr| self.height(nbr) == start_height + 1)
-                    .flat_map(|nbr| self.find_peaks(nbr)),
-            )
+    fn find_trailheads(&self) -> impl Iterator<Item = Position> + '_ {
+        self.positions_at(0)
+    }
+
+    fn positions_at(&self, height: Height) -> impl Iterator<Item = Position> + '_ {
+        let (n_rows, n_cols) = self.bounds();
+        (0..n_rows)
+            .flat_map(move |i| (0..n_cols).map(move |j| (i, j)))
+            .filter(move |pos| self.height(pos) == height)
+    }
+
+    fn uphill_nbrs(&self, pos: Position) -> impl Iterator<Item = Position> + '_ {
+        let height = self.height(&pos);
+        self.nbrs(pos)
+            .filter(move |nbr| self.height(nbr) == height + 1)
+    }
+
+    // Works down from the peaks, so every cell's uphill neighbours are done before it.
+    // Trails are counted over the whole grid, peaks a tile of trailheads at a time
+    fn trail_table(&self) -> TrailTable {
+        let (n_rows, n_cols) = self.bounds();
+        let mut n_trails = vec![vec![0; n_cols]; n_rows];
+        for height in (0..=9).rev() {
+            for pos in self.positions_at(height) {
+                n_trails[pos.0][pos.1] = if height == 9 {
+                    1
+                } else {
+                    self.uphill_nbrs(pos)
+                        .map(|nbr| n_trails[nbr.0][nbr.1])
+                        .sum()
+                };
+            }
+        }
+        let mut n_peaks = vec![vec![0; n_cols]; n_rows];
+        for top in (0..n_rows).step_by(TILE) {
+            for left in (0..n_cols).step_by(TILE) {
+                self.count_peaks_in_tile((top, left), &mut n_peaks);
+            }
         }
+        TrailTable { n_peaks, n_trails }
     }
 
-    fn find_trailheads(&self) -> impl Iterator<Item = Position> + '_ {
-        let (height, width) = self.bounds();
-        (0..height)
-            .flat_map(move |i| (0..width).map(move |j| (i, j)))
-            .filter(|pos| self.height(pos) == 0)
+    // A trail never gets more than 9 steps from its trailhead, so only the peaks in the tile
+    // padded by 9 matter. Numbering just those keeps the bitsets a few words long, whatever
+    // the size of the grid. Cells near the padding's edge may miss peaks, but none of them are
+    // on a trail from the tile
+    fn count_peaks_in_tile(&self, top_left: Position, n_peaks: &mut [Vec<usize>]) {
+        let (n_rows, n_cols) = self.bounds();
+        let rows = top_left.0.saturating_sub(9)..(top_left.0 + TILE + 9).min(n_rows);
+        let cols = top_left.1.saturating_sub(9)..(top_left.1 + TILE + 9).min(n_cols);
+        let in_window = |pos: &Position| rows.contains(&pos.0) && cols.contains(&pos.1);
+        let local = |pos: Position| (pos.0 - rows.start) * cols.len() + (pos.1 - cols.start);
+        let cells: Vec<Position> = rows
+            .clone()
+            .flat_map(|i| cols.clone().map(move |j| (i, j)))
+            .collect();
+        let n_local_peaks = cells.iter().filter(|pos| self.height(pos) == 9).count();
+        let mut reachable = vec![PeakSet::empty(0); cells.len()];
+        let mut next_peak = 0;
+        for height in (0..=9).rev() {
+            for pos in cells.iter().filter(|pos| self.height(pos) == height) {
+                let mut peaks = PeakSet::empty(n_local_peaks);
+                if height == 9 {
+                    peaks.insert(next_peak);
+                    next_peak += 1;
+                }
+                for nbr in self.uphill_nbrs(*pos).filter(in_window) {
+                    peaks.union_with(&reachable[local(nbr)]);
+                }
+                reachable[local(*pos)] = peaks;
+            }
+        }
+        let tile_rows = top_left.0..top_left.0 + TILE;
+        let tile_cols = top_left.1..top_left.1 + TILE;
+        for pos in cells.iter().filter(|pos| self.height(pos) == 0) {
+            if tile_rows.contains(&pos.0) && tile_cols.contains(&pos.1) {
+                n_peaks[pos.0][pos.1] = reachable[local(*pos)].len();
+            }
+        }
+    }
+
+    // Every distinct trail from the trailhead, skipping cells that can't reach a peak
+    fn trails_from(&self, table: &TrailTable, trailhead: Position) -> Vec<Vec<Position>> {
+        let mut trails = vec![];
+        let mut stack = vec![vec![trailhead]];
+        while let Some(trail) = stack.pop() {
+            let end = *trail.last().unwrap();
+            if self.height(&end) == 9 {
+                trails.push(trail);
+                continue;
+            }
+            for nbr in self.uphill_nbrs(end) {
+                if table.n_trails[nbr.0][nbr.1] > 0 {
+                    let mut longer = trail.clone();
+                    longer.push(nbr);
+                    stack.push(longer);
+                }
+            }
+        }
+        trails.reverse();
+        trails
+    }
+
+    // Heights on any trail from the trailhead are highlighted, brighter the more trails use them
+    fn render_trails(&self, trails: &[Vec<Position>]) -> String {
+        let mut uses: HashMap<Position, usize> = HashMap::new();
+        for pos in trails.iter().flatten() {
+            *uses.entry(*pos).or_default() += 1;
+        }
+        let most_uses = uses.values().copied().max().unwrap_or(1);
+        let (n_rows, n_cols) = self.bounds();
+        let mut output = String::new();
+        for i in 0..n_rows {
+            for j in 0..n_cols {
+                let height = self.height(&(i, j)).to_string();
+                let cell = match uses.get(&(i, j)) {
+                    Some(n) => {
+                        let brightness = (96 + 159 * n / most_uses) as u8;
+                        height.bold().truecolor(0, brightness, 0)
+                    }
+                    None => height.dimmed(),
+                };
+                output.push_str(&cell.to_string());
+            }
+            output.push('\n');
+        }
+        output
+    }
+}
+
+// Side of the square tiles of trailheads whose peaks are counted together
+const TILE: usize = 32;
+
+// A set of peaks, by index, as a bitset
+#[derive(Clone)]
+struct PeakSet(Vec<u64>);
+
+impl PeakSet {
+    fn empty(n_peaks: usize) -> Self {
+        Self(vec![0; n_peaks.div_ceil(64)])
+    }
+
+    fn insert(&mut self, idx: usize) {
+        self.0[idx / 64] |= 1 << (idx % 64);
+    }
+
+    fn union_with(&mut self, other: &PeakSet) {
+        for (word, other_word) in self.0.iter_mut().zip(&other.0) {
+            *word |= other_word;
+        }
     }
 
+    fn len(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+}
+
+// How many peaks can be reached from each trailhead, and by how many trails from every cell
+struct TrailTable {
+    n_peaks: Vec<Vec<usize>>,
+    n_trails: Vec<Vec<usize>>,
+}
+
+impl TrailTable {
     fn score_a_trailhead(&self, trailhead: Position) -> usize {
-        let peaks: HashSet<Position> = self.find_peaks(trailhead).collect();
-        peaks.len()
+        self.n_peaks[trailhead.0][trailhead.1]
     }
 
     fn score_b_trailhead(&self, trailhead: Position) -> usize {
-        self.find_peaks(trailhead).map(|_| 1).sum()
+        self.n_trails[trailhead.0][trailhead.1]
     }
 }
 
@@ -76,19 +222,96 @@ fn parse(input: &str) -> Grid {
 impl ProblemSolution for Solution {
     fn solve_a(&self, input: &str) -> Option<String> {
         let grid = parse(input);
+        let table = grid.trail_table();
         let answer: usize = grid
             .find_trailheads()
-            .map(|t| grid.score_a_trailhead(t))
+            .map(|t| table.score_a_trailhead(t))
             .sum();
         Some(answer.to_string())
     }
 
     fn solve_b(&self, input: &str) -> Option<String> {
         let grid = parse(input);
+        let table = grid.trail_table();
         let answer: usize = grid
             .find_trailheads()
-            .map(|t| grid.score_b_trailhead(t))
+            .map(|t| table.score_b_trailhead(t))
             .sum();
         Some(answer.to_string())
     }
+
+    // trails <row> <col> [render] : every distinct trail from the trailhead at (row, col), either
+    // listed or drawn on the map
+    fn run_tool(&self, input: &str, args: &[String]) -> Result<String, ToolError> {
+        if args[0] != "trails" {
+            return Err(ToolError::Unknown);
+        }
+        let grid = parse(input);
+        let parse_arg = |idx: usize| {
+            let arg = args
+                .get(idx)
+                .ok_or_else(|| ToolError::BadArgs("Expected a row and column".to_string()))?;
+            arg.parse::<usize>()
+                .map_err(|_e| ToolError::BadArgs(format!("Could not parse \"{arg}\"")))
+        };
+        let trailhead = (parse_arg(1)?, parse_arg(2)?);
+        let (n_rows, n_cols) = grid.bounds();
+        if trailhead.0 >= n_rows || trailhead.1 >= n_cols || grid.height(&trailhead) != 0 {
+            return Err(ToolError::BadArgs(format!("No trailhead at {trailhead:?}")));
+        }
+        let table = grid.trail_table();
+        let trails = grid.trails_from(&table, trailhead);
+        let mut output = format!(
+            "{} trails reaching {} peaks\n",
+            table.score_b_trailhead(trailhead),
+            table.score_a_trailhead(trailhead)
+        );
+        match args.get(3).map(|arg| arg.as_str()) {
+            Some("render") => output.push_str(&grid.render_trails(&trails)),
+            Some(arg) => return Err(ToolError::BadArgs(format!("Unexpected \"{arg}\""))),
+            None => {
+                for trail in trails {
+                    let steps: Vec<String> = trail.iter().map(|pos| format!("{pos:?}")).collect();
+                    output.push_str(&steps.join(" -> "));
+                    output.push('\n');
+                }
+            }
+        }
+        Ok(output)
+    }
+
+    // A size x size map of hills: each cell's height falls away with the distance to the nearest
+    // summit (wrapping round to 9 again far away), with one cell in twenty random
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        // Breadth first from every summit at once finds each cell's distance to the nearest
+        let mut distance = vec![vec![usize::MAX; size]; size];
+        let mut queue = VecDeque::new();
+        for _ in 0..(size * size / 40).max(1) {
+            let summit = (rng.range(0..=size - 1), rng.range(0..=size - 1));
+            distance[summit.0][summit.1] = 0;
+            queue.push_back(summit);
+        }
+        let grid = Grid(vec![vec![0; size]; size]);
+        while let Some(pos) = queue.pop_front() {
+            for nbr in grid.nbrs(pos) {
+                if distance[nbr.0][nbr.1] == usize::MAX {
+                    distance[nbr.0][nbr.1] = distance[pos.0][pos.1] + 1;
+                    queue.push_back(nbr);
+                }
+            }
+        }
+        let mut output = String::new();
+        for row in distance {
+            for dist in row {
+                let height = if rng.one_in(20) {
+                    rng.range(0..=9)
+                } else {
+                    9 - dist % 10
+                };
+                output.push(char::from_digit(height as u32, 10).unwrap());
+            }
+            output.push('\n');
+        }
+        Some(output)
+    }
 }
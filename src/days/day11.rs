@@ -1,8 +1,12 @@
-use std::collections::HashMap;
+use std::ops::AddAssign;
 
+use crate::problem::ToolError;
+use crate::rng::Rng;
 use crate::ProblemSolution;
 #[allow(unused_imports)]
 use aoc_parse::{parser, prelude::*};
+use num_bigint::BigUint;
+use rustc_hash::{FxHashMap, FxHashSet};
 pub struct Solution {}
 
 fn n_digits(num: &usize) -> u32 {
@@ -11,7 +15,7 @@ fn n_digits(num: &usize) -> u32 {
 
 fn try_split(num: usize) -> Option<(usize, usize)> {
     let n_digits = n_digits(&num);
-    if n_digits % 2 == 0 {
+    if n_digits.is_multiple_of(2) {
         let power_of_10 = 10usize.pow(n_digits / 2);
         let first_half = num / power_of_10;
         let second_half = num % power_of_10;
@@ -31,23 +35,89 @@ fn single_blink(stone: usize) -> Vec<usize> {
     }
 }
 
-fn blink(stone: usize, count: usize, cache: &mut HashMap<(usize, usize), usize>) -> usize {
-    if count == 0 {
-        return 1;
+// Stones never interact and their order never matters, so we only need to know
+// how many stones have each value. C is the count type, so we can swap in big integers.
+struct StoneCounts<C>(FxHashMap<usize, C>);
+
+impl<C> StoneCounts<C>
+where
+    C: Default + From<u8> + for<'a> AddAssign<&'a C>,
+{
+    fn init(stones: &[usize]) -> Self {
+        let mut counts: FxHashMap<usize, C> = FxHashMap::default();
+        for stone in stones {
+            *counts.entry(*stone).or_default() += &C::from(1);
+        }
+        Self(counts)
     }
 
-    if cache.contains_key(&(stone, count)) {
-        return cache.get(&(stone, count)).unwrap().clone();
+    fn blink(&self) -> Self {
+        let mut counts: FxHashMap<usize, C> = FxHashMap::default();
+        for (stone, count) in self.0.iter() {
+            for new_stone in single_blink(*stone) {
+                *counts.entry(new_stone).or_default() += count;
+            }
+        }
+        Self(counts)
     }
 
-    let answer = single_blink(stone)
-        .into_iter()
-        .map(|st| blink(st, count - 1, cache))
-        .sum();
+    fn total(&self) -> C {
+        let mut total = C::default();
+        for count in self.0.values() {
+            total += count;
+        }
+        total
+    }
+}
 
-    cache.insert((stone, count), answer);
+fn count_after_blinks(stones: &[usize], blinks: usize) -> u64 {
+    let mut counts: StoneCounts<u64> = StoneCounts::init(stones);
+    for _ in 0..blinks {
+        counts = counts.blink();
+    }
+    counts.total()
+}
 
-    answer
+// Big numbers are abbreviated to their number of digits
+fn abbreviate(num: &BigUint) -> String {
+    let num = num.to_string();
+    if num.len() > 20 {
+        format!("<{} digits>", num.len())
+    } else {
+        num
+    }
+}
+
+// Runs with arbitrary precision and reports, every `every` blinks, how many distinct values
+// are on the stones and how many stones there are. Also tracks every value that has ever appeared
+// which (for realistic inputs) stops growing after a few dozen blinks
+fn blink_statistics(stones: &[usize], blinks: usize, every: usize) -> String {
+    let mut counts: StoneCounts<BigUint> = StoneCounts::init(stones);
+    let mut reachable: FxHashSet<usize> = counts.0.keys().copied().collect();
+    let mut closed_at = 0;
+    let mut output = String::new();
+    for blink in 0..=blinks {
+        if blink > 0 {
+            counts = counts.blink();
+            let n_reachable = reachable.len();
+            reachable.extend(counts.0.keys().copied());
+            if reachable.len() > n_reachable {
+                closed_at = blink;
+            }
+        }
+        if blink % every == 0 || blink == blinks {
+            output.push_str(&format!(
+                "Blink {blink}: {} distinct values, {} stones\n",
+                counts.0.len(),
+                abbreviate(&counts.total())
+            ));
+        }
+    }
+    output.push_str(&format!(
+        "{} values reachable, the last new value appearing at blink {closed_at}\n",
+        reachable.len()
+    ));
+    output
 }
 
 fn parse(input: &str) -> Vec<usize> {
@@ -57,15 +127,38 @@ fn parse(input: &str) -> Vec<usize> {
 impl ProblemSolution for Solution {
     fn solve_a(&self, input: &str) -> Option<String> {
         let stones = parse(input);
-        let mut cache = HashMap::default();
-        let answer: usize = stones.into_iter().map(|st| blink(st, 25, &mut cache)).sum();
+        let answer = count_after_blinks(&stones, 25);
         Some(answer.to_string())
     }
 
     fn solve_b(&self, input: &str) -> Option<String> {
         let stones = parse(input);
-        let mut cache = HashMap::default();
-        let answer: usize = stones.into_iter().map(|st| blink(st, 75, &mut cache)).sum();
+        let answer = count_after_blinks(&stones, 75);
         Some(answer.to_string())
     }
+
+    // stats [blinks] [every] : distinct values and stone counts per blink (default 75 blinks, every blink)
+    fn run_tool(&self, input: &str, args: &[String]) -> Result<String, ToolError> {
+        if args[0] != "stats" {
+            return Err(ToolError::Unknown);
+        }
+        let parse_arg = |idx: usize, default: usize| match args.get(idx) {
+            Some(arg) => arg
+                .parse()
+                .map_err(|_e| ToolError::BadArgs(format!("Could not parse \"{arg}\""))),
+            None => Ok(default),
+        };
+        let blinks = parse_arg(1, 75)?;
+        let every = parse_arg(2, 1)?.max(1);
+        let stones = parse(input);
+        Ok(blink_statistics(&stones, blinks, every))
+    }
+
+    // size stones engraved with numbers below a million
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        let stones: Vec<String> = (0..size)
+            .map(|_| rng.range(0..=999_999).to_string())
+            .collect();
+        Some(stones.join(" ") + "\n")
+    }
 }
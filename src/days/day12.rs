@@ -1,8 +1,11 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
+use crate::problem::ToolError;
+use crate::rng::Rng;
 use crate::ProblemSolution;
 #[allow(unused_imports)]
 use aoc_parse::{parser, prelude::*};
+use colored::Colorize;
 pub struct Solution {}
 
 type Position = (usize, usize);
@@ -35,13 +38,7 @@ impl<'a> GridPosition<'a> {
         let other_pos = (posi.0 + delta.0, posi.1 + delta.1);
         match self.grid.try_position(other_pos) {
             // Nbr on grid => check if label matches
-            Some(other) => {
-                if other.label() != self.label() {
-                    true
-                } else {
-                    false
-                }
-            }
+            Some(other) => other.label() != self.label(),
             // Nbr off grid => perimeter
             None => true,
         }
@@ -78,26 +75,6 @@ impl<'a> GridPosition<'a> {
             })
             .count()
     }
-
-    fn get_region(&self) -> HashSet<Position> {
-        let mut region = HashSet::new();
-        let mut to_visit = HashSet::new();
-        to_visit.insert(self.position);
-        while let Some(next_pos) = to_visit.iter().next().cloned() {
-            // Remove from to_visit
-            to_visit.remove(&next_pos);
-            // Look for nbrs with same label
-            let next_pos = self.grid.position(next_pos);
-            for nbr in next_pos.nbrs() {
-                if nbr.label() == next_pos.label() && !region.contains(&nbr.position) {
-                    to_visit.insert(nbr.position);
-                }
-            }
-            // Add to the region
-            region.insert(next_pos.position);
-        }
-        region
-    }
 }
 
 fn rotate(delta: (isize, isize)) -> (isize, isize) {
@@ -139,55 +116,275 @@ impl Grid {
         }
     }
 
-    fn get_all_regions(&self) -> Vec<HashSet<Position>> {
-        let mut regions = vec![];
+    // Union-find over the cells, merging each cell with its right and lower neighbours
+    // when they share a label. Returns the region index of every cell and each region's cells
+    fn label_regions(&self) -> (Vec<Vec<usize>>, Vec<Vec<Position>>) {
         let (height, width) = self.bounds();
-        let mut remaining: HashSet<_> = (0..height)
-            .flat_map(|i| (0..width).map(move |j| (i, j)))
-            .collect();
-        while let Some(next_pos) = remaining.iter().next().cloned() {
-            let next_region = self.position(next_pos).get_region();
-            for pos in next_region.iter() {
-                remaining.remove(&pos);
+        let mut sets = DisjointSets::init(height * width);
+        for i in 0..height {
+            for j in 0..width {
+                if j + 1 < width && self.labels[i][j] == self.labels[i][j + 1] {
+                    sets.union(i * width + j, i * width + j + 1);
+                }
+                if i + 1 < height && self.labels[i][j] == self.labels[i + 1][j] {
+                    sets.union(i * width + j, (i + 1) * width + j);
+                }
+            }
+        }
+        let mut region_of_root = HashMap::new();
+        let mut region_map = vec![vec![0; width]; height];
+        let mut regions: Vec<Vec<Position>> = vec![];
+        for (i, row) in region_map.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                let root = sets.find(i * width + j);
+                let region = *region_of_root.entry(root).or_insert_with(|| {
+                    regions.push(vec![]);
+                    regions.len() - 1
+                });
+                *cell = region;
+                regions[region].push((i, j));
             }
-            regions.push(next_region);
         }
-        regions
+        (region_map, regions)
     }
 
-    fn price_region_a(&self, region: &HashSet<Position>) -> usize {
-        let area = region.len();
-        let perim: usize = region
+    fn summarise(&self, cells: Vec<Position>) -> Region {
+        let plant = self.position(cells[0]).label();
+        let top_left = (
+            cells.iter().map(|p| p.0).min().unwrap(),
+            cells.iter().map(|p| p.1).min().unwrap(),
+        );
+        let bottom_right = (
+            cells.iter().map(|p| p.0).max().unwrap(),
+            cells.iter().map(|p| p.1).max().unwrap(),
+        );
+        let perimeter = cells
             .iter()
             .map(|p| self.position(*p).perimiter_contribution())
             .sum();
-        area * perim
+        // A polygon has as many sides as it has corners
+        let sides = cells.iter().map(|p| self.position(*p).corner_count()).sum();
+        let holes = count_holes(&cells, top_left, bottom_right);
+        Region {
+            plant,
+            area: cells.len(),
+            perimeter,
+            sides,
+            top_left,
+            bottom_right,
+            holes,
+        }
     }
 
-    fn price_region_b(&self, region: &HashSet<Position>) -> usize {
-        let area = region.len();
-        let corners: usize = region
-            .iter()
-            .map(|p| self.position(*p).corner_count())
-            .sum();
-        area * corners
+    fn regions(&self) -> Vec<Region> {
+        let (_region_map, regions) = self.label_regions();
+        regions.into_iter().map(|r| self.summarise(r)).collect()
+    }
+
+    // Each region coloured so that no two touching regions share a colour
+    fn render(&self) -> String {
+        const PALETTE: [(u8, u8, u8); 8] = [
+            (230, 25, 75),
+            (60, 180, 75),
+            (255, 225, 25),
+            (0, 130, 200),
+            (245, 130, 48),
+            (145, 30, 180),
+            (70, 240, 240),
+            (240, 50, 230),
+        ];
+        let (region_map, regions) = self.label_regions();
+        let (height, width) = self.bounds();
+        let mut colours: Vec<Option<usize>> = vec![None; regions.len()];
+        for (region, cells) in regions.iter().enumerate() {
+            let used: HashSet<usize> = cells
+                .iter()
+                .flat_map(|p| {
+                    self.position(*p)
+                        .nbrs()
+                        .map(|n| n.position)
+                        .collect::<Vec<_>>()
+                })
+                .filter_map(|n| colours[region_map[n.0][n.1]])
+                .collect();
+            // A region can touch more regions than we have colours, in which case we reuse one
+            let colour = (0..PALETTE.len())
+                .find(|c| !used.contains(c))
+                .unwrap_or(region % PALETTE.len());
+            colours[region] = Some(colour);
+        }
+        let mut output = String::new();
+        for i in 0..height {
+            for j in 0..width {
+                let (r, g, b) = PALETTE[colours[region_map[i][j]].unwrap()];
+                let cell = self.labels[i][j].to_string().black().on_truecolor(r, g, b);
+                output.push_str(&cell.to_string());
+            }
+            output.push('\n');
+        }
+        output
     }
 }
 
+struct DisjointSets {
+    parents: Vec<usize>,
+    sizes: Vec<usize>,
+}
+
+impl DisjointSets {
+    fn init(n: usize) -> Self {
+        Self {
+            parents: (0..n).collect(),
+            sizes: vec![1; n],
+        }
+    }
+
+    fn find(&mut self, x: usize) -> usize {
+        let mut root = x;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        // Path compression
+        let mut x = x;
+        while self.parents[x] != root {
+            let next = self.parents[x];
+            self.parents[x] = root;
+            x = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        // Union by size
+        if self.sizes[a] < self.sizes[b] {
+            (a, b) = (b, a);
+        }
+        self.parents[b] = a;
+        self.sizes[a] += self.sizes[b];
+    }
+}
+
+struct Region {
+    plant: char,
+    area: usize,
+    perimeter: usize,
+    sides: usize,
+    top_left: Position,
+    bottom_right: Position,
+    holes: usize,
+}
+
+impl Region {
+    fn price_a(&self) -> usize {
+        self.area * self.perimeter
+    }
+
+    fn price_b(&self) -> usize {
+        self.area * self.sides
+    }
+}
+
+// Flood the complement of the region within its bounding box (padded by one cell) from outside.
+// Every other component of the complement is a hole. Adjacency is 4-connected as in the
+// puzzle, so a pocket touching the outside only at a corner still counts as enclosed.
+fn count_holes(cells: &[Position], top_left: Position, bottom_right: Position) -> usize {
+    let height = bottom_right.0 - top_left.0 + 3;
+    let width = bottom_right.1 - top_left.1 + 3;
+    let mut filled = vec![vec![false; width]; height];
+    for p in cells {
+        filled[p.0 - top_left.0 + 1][p.1 - top_left.1 + 1] = true;
+    }
+    let flood = |start: Position, filled: &mut [Vec<bool>]| {
+        let mut to_visit = vec![start];
+        filled[start.0][start.1] = true;
+        while let Some((i, j)) = to_visit.pop() {
+            for (di, dj) in [(-1, 0), (0, 1), (1, 0), (0, -1)] {
+                let (ni, nj) = (i as isize + di, j as isize + dj);
+                if ni < 0 || nj < 0 || ni >= height as isize || nj >= width as isize {
+                    continue;
+                }
+                let (ni, nj) = (ni as usize, nj as usize);
+                if !filled[ni][nj] {
+                    filled[ni][nj] = true;
+                    to_visit.push((ni, nj));
+                }
+            }
+        }
+    };
+    flood((0, 0), &mut filled);
+    let mut holes = 0;
+    for i in 0..height {
+        for j in 0..width {
+            if !filled[i][j] {
+                flood((i, j), &mut filled);
+                holes += 1;
+            }
+        }
+    }
+    holes
+}
+
 impl ProblemSolution for Solution {
     fn solve_a(&self, input: &str) -> Option<String> {
         let labels = parser!(lines(upper+)).parse(input).unwrap();
         let grid = Grid::init(labels);
-        let regions = grid.get_all_regions();
-        let price: usize = regions.into_iter().map(|r| grid.price_region_a(&r)).sum();
+        let price: usize = grid.regions().iter().map(|r| r.price_a()).sum();
         Some(price.to_string())
     }
 
     fn solve_b(&self, input: &str) -> Option<String> {
         let labels = parser!(lines(upper+)).parse(input).unwrap();
         let grid = Grid::init(labels);
-        let regions = grid.get_all_regions();
-        let price: usize = regions.into_iter().map(|r| grid.price_region_b(&r)).sum();
+        let price: usize = grid.regions().iter().map(|r| r.price_b()).sum();
         Some(price.to_string())
     }
+
+    // render : the map with each region in its own colour
+    // regions : area, perimeter, sides, bounding box and number of holes of every region
+    fn run_tool(&self, input: &str, args: &[String]) -> Result<String, ToolError> {
+        let labels = parser!(lines(upper+)).parse(input).unwrap();
+        let grid = Grid::init(labels);
+        match args[0].as_str() {
+            "render" => Ok(grid.render()),
+            "regions" => Ok(grid
+                .regions()
+                .into_iter()
+                .map(|r| {
+                    format!(
+                        "{}: area {}, perimeter {}, sides {}, box {:?}-{:?}, {} holes\n",
+                        r.plant, r.area, r.perimeter, r.sides, r.top_left, r.bottom_right, r.holes
+                    )
+                })
+                .collect()),
+            _ => Err(ToolError::Unknown),
+        }
+    }
+
+    // A size x size garden where each plot takes the plant of its nearest seed, giving
+    // irregular regions that sometimes surround each other
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        let plants: Vec<char> = ('A'..='Z').collect();
+        let seeds: Vec<((usize, usize), char)> = (0..(size * size / 30).max(1))
+            .map(|_| {
+                let pos = (rng.range(0..=size - 1), rng.range(0..=size - 1));
+                (pos, *rng.pick(&plants))
+            })
+            .collect();
+        let mut output = String::new();
+        for i in 0..size {
+            for j in 0..size {
+                let (_pos, plant) = seeds
+                    .iter()
+                    .min_by_key(|(pos, _plant)| pos.0.abs_diff(i) + pos.1.abs_diff(j))
+                    .unwrap();
+                output.push(*plant);
+            }
+            output.push('\n');
+        }
+        Some(output)
+    }
 }
@@ -1,9 +1,9 @@
+use crate::linalg::{bareiss_solve, cheapest_two_term_solution, LinearSolution};
+use crate::rng::Rng;
 use crate::ProblemSolution;
 #[allow(unused_imports)]
 use aoc_parse::{parser, prelude::*};
 
-use rational::Rational;
-
 pub struct Solution {}
 
 #[derive(PartialEq, Eq, Hash)]
@@ -13,98 +13,78 @@ struct Machine {
     target: (u64, u64),
 }
 
-#[allow(dead_code)]
-fn print_row(mat: &[Rational; 3]) {
-    print!("{} {} {}", mat[0], mat[1], mat[2])
-}
-
-#[allow(dead_code)]
-fn print_mat(mat: &[[Rational; 3]; 2]) {
-    print_row(&mat[0]);
-    println!("");
-    print_row(&mat[1]);
-    println!("");
-}
-
-fn mult_row(row: &[Rational; 3], mult: Rational) -> [Rational; 3] {
-    [row[0] * mult, row[1] * mult, row[2] * mult]
-}
-fn divide_row(row: &[Rational; 3], div: Rational) -> [Rational; 3] {
-    [row[0] / div, row[1] / div, row[2] / div]
-}
-
-fn add_row(a: &[Rational; 3], b: &[Rational; 3]) -> [Rational; 3] {
-    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
-}
-
-// Return RREF
-fn row_reduce(mut am: [[Rational; 3]; 2]) -> Option<[[Rational; 3]; 2]> {
-    am[0] = divide_row(&am[0], am[0][0]);
-    am[1] = add_row(&am[1], &mult_row(&am[0], -am[1][0]));
-    if am[1][1] == Rational::new(0, 1) {
-        // Inconsistent system, no solution over Q?
-        return None;
-    }
-    am[1] = divide_row(&am[1], am[1][1]);
-    am[0] = add_row(&am[0], &mult_row(&am[1], -am[0][1]));
-
-    return Some(am);
-}
+const COST_A: i128 = 3;
+const COST_B: i128 = 1;
 
 impl Machine {
-    fn augmented_matrix(&self) -> [[Rational; 3]; 2] {
-        [
-            [
-                self.button_a.0.into(),
-                self.button_b.0.into(),
-                self.target.0.into(),
+    fn augmented_matrix(&self) -> Vec<Vec<i128>> {
+        vec![
+            vec![
+                self.button_a.0 as i128,
+                self.button_b.0 as i128,
+                self.target.0 as i128,
             ],
-            [
-                self.button_a.1.into(),
-                self.button_b.1.into(),
-                self.target.1.into(),
+            vec![
+                self.button_a.1 as i128,
+                self.button_b.1 as i128,
+                self.target.1 as i128,
             ],
         ]
     }
 
-    // Try and solve over Q (assume unique solution)
-    // Only return solution of it happens to lie in Z^2
-    fn fancy_solve(&self) -> Option<u64> {
-        let am = self.augmented_matrix();
-        let red = row_reduce(am).unwrap();
-        if red[0][2].denominator() == 1 && red[1][2].denominator() == 1 {
-            let (a_presses, _) = red[0][2].mixed_fraction();
-            let (b_presses, _) = red[1][2].mixed_fraction();
-            let a_presses = a_presses as u64;
-            let b_presses = b_presses as u64;
-            Some(3 * a_presses + b_presses)
-        } else {
-            None
-        }
+    // Fewest tokens needed to win the prize, if it can be won at all
+    fn cheapest_win(&self, max_presses: Option<u64>) -> Option<u64> {
+        let max_presses = max_presses.map(|m| m as i128);
+        let within_limit = |presses: i128| presses >= 0 && max_presses.is_none_or(|m| presses <= m);
+        let (a_presses, b_presses) = match bareiss_solve(self.augmented_matrix()) {
+            LinearSolution::Unique {
+                numerators,
+                denominator,
+            } => {
+                // Unique solution over Q, only any good if it lies in Z^2
+                if numerators.iter().any(|n| n % denominator != 0) {
+                    return None;
+                }
+                (numerators[0] / denominator, numerators[1] / denominator)
+            }
+            LinearSolution::Singular => self.cheapest_collinear_win(max_presses)?,
+        };
+        (within_limit(a_presses) && within_limit(b_presses))
+            .then(|| (COST_A * a_presses + COST_B * b_presses) as u64)
     }
 
-    fn solve(&self) -> Option<u64> {
-        (0..100)
-            .take_while(|a_presses| {
-                let a_endpoint = (self.button_a.0 * a_presses, self.button_a.1 * a_presses);
-                a_endpoint.0 <= self.target.0 && a_endpoint.1 <= self.target.1
-            })
-            .filter_map(|a_presses| {
-                let a_endpoint = (self.button_a.0 * a_presses, self.button_a.1 * a_presses);
-                let remaining = (self.target.0 - a_endpoint.0, self.target.1 - a_endpoint.1);
-                if remaining.0.rem_euclid(self.button_b.0) != 0 {
-                    None
-                } else {
-                    let b_presses = remaining.0 / self.button_b.0;
-                    let b_total_1 = self.button_b.1 * b_presses;
-                    if b_total_1 != remaining.1 {
-                        None
-                    } else {
-                        Some(3 * a_presses + b_presses)
-                    }
-                }
-            })
-            .min()
+    // Both buttons move along the same line, so the prize must lie on it too.
+    // Then the problem is one dimensional, in whichever axis the line isn't perpendicular to.
+    fn cheapest_collinear_win(&self, max_presses: Option<i128>) -> Option<(i128, i128)> {
+        let cross =
+            |u: (u64, u64), v: (u64, u64)| u.0 as i128 * v.1 as i128 - u.1 as i128 * v.0 as i128;
+        // Neither button moves the claw, so only a prize at the origin is won (for free)
+        if self.button_a == (0, 0) && self.button_b == (0, 0) {
+            return (self.target == (0, 0)).then_some((0, 0));
+        }
+        let direction = if self.button_a != (0, 0) {
+            self.button_a
+        } else {
+            self.button_b
+        };
+        if cross(direction, self.target) != 0 {
+            return None;
+        }
+        let coefficients = if direction.0 != 0 {
+            (self.button_a.0, self.button_b.0, self.target.0)
+        } else {
+            (self.button_a.1, self.button_b.1, self.target.1)
+        };
+        cheapest_two_term_solution(
+            (
+                coefficients.0 as i128,
+                coefficients.1 as i128,
+                coefficients.2 as i128,
+            ),
+            (COST_A, COST_B),
+            max_presses,
+            max_presses,
+        )
     }
 }
 
@@ -125,7 +105,10 @@ fn parse(input: &str) -> Vec<Machine> {
 impl ProblemSolution for Solution {
     fn solve_a(&self, input: &str) -> Option<String> {
         let machines = parse(input);
-        let total_cost: u64 = machines.into_iter().filter_map(|m| m.solve()).sum();
+        let total_cost: u64 = machines
+            .into_iter()
+            .filter_map(|m| m.cheapest_win(Some(100)))
+            .sum();
         Some(total_cost.to_string())
     }
 
@@ -135,7 +118,31 @@ impl ProblemSolution for Solution {
             m.target.0 += 10000000000000;
             m.target.1 += 10000000000000;
         }
-        let total_cost: u64 = machines.into_iter().filter_map(|m| m.fancy_solve()).sum();
+        let total_cost: u64 = machines
+            .into_iter()
+            .filter_map(|m| m.cheapest_win(None))
+            .sum();
         Some(total_cost.to_string())
     }
+
+    // size machines, half of which can be won within 100 presses of each button
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        let machines: Vec<String> = (0..size)
+            .map(|_| {
+                let a = (rng.range(10..=99), rng.range(10..=99));
+                let b = (rng.range(10..=99), rng.range(10..=99));
+                let target = if rng.one_in(2) {
+                    let (n_a, n_b) = (rng.range(0..=100), rng.range(0..=100));
+                    (n_a * a.0 + n_b * b.0, n_a * a.1 + n_b * b.1)
+                } else {
+                    (rng.range(1000..=20000), rng.range(1000..=20000))
+                };
+                format!(
+                    "Button A: X+{}, Y+{}\nButton B: X+{}, Y+{}\nPrize: X={}, Y={}\n",
+                    a.0, a.1, b.0, b.1, target.0, target.1
+                )
+            })
+            .collect();
+        Some(machines.join("\n"))
+    }
 }
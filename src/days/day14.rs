@@ -1,7 +1,11 @@
+use std::fs;
+
+use crate::linalg::extended_gcd;
+use crate::problem::ToolError;
+use crate::rng::Rng;
 use crate::ProblemSolution;
 #[allow(unused_imports)]
 use aoc_parse::{parser, prelude::*};
-use colored::Colorize;
 use counter::Counter;
 pub struct Solution {}
 
@@ -56,22 +60,109 @@ fn parse(input: &str) -> Vec<Robot> {
     .unwrap()
 }
 
-fn display_robots(robots: &Vec<Robot>, bounds: (isize, isize)) {
-    let counts = robots
-        .iter()
-        .map(|r| r.position)
-        .collect::<Counter<(isize, isize)>>();
+// The example and the real input use different arenas, and nothing in the input says which.
+// Pick the smallest known arena that fits every robot, or failing that the smallest arena that does.
+const KNOWN_ARENAS: [(isize, isize); 2] = [(11, 7), (101, 103)];
+
+fn detect_bounds(robots: &[Robot]) -> (isize, isize) {
+    let needed = (
+        robots.iter().map(|r| r.position.0).max().unwrap_or(0) + 1,
+        robots.iter().map(|r| r.position.1).max().unwrap_or(0) + 1,
+    );
+    KNOWN_ARENAS
+        .into_iter()
+        .find(|arena| arena.0 >= needed.0 && arena.1 >= needed.1)
+        .unwrap_or(needed)
+}
+
+fn robot_counts(robots: &[Robot]) -> Counter<(isize, isize)> {
+    robots.iter().map(|r| r.position).collect()
+}
+
+#[derive(Clone, Copy)]
+enum FrameFormat {
+    Text,
+    Pbm,
+    Pgm,
+}
+
+impl FrameFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            FrameFormat::Text => "txt",
+            FrameFormat::Pbm => "pbm",
+            FrameFormat::Pgm => "pgm",
+        }
+    }
+}
+
+// Plain (ASCII) PBM/PGM so frames can be printed as well as written to disk
+// PGM shades each tile by how many robots are on it
+fn render_frame(robots: &[Robot], bounds: (isize, isize), format: FrameFormat) -> String {
+    let counts = robot_counts(robots);
+    let max_count = counts.values().copied().max().unwrap_or(1);
+    let mut frame = match format {
+        FrameFormat::Text => String::new(),
+        FrameFormat::Pbm => format!("P1\n{} {}\n", bounds.0, bounds.1),
+        FrameFormat::Pgm => format!("P2\n{} {}\n{max_count}\n", bounds.0, bounds.1),
+    };
     for y in 0..bounds.1 {
-        for x in 0..bounds.0 {
-            let count = counts.get(&(x, y)).copied().unwrap_or(0);
-            if count == 0 {
-                print!(".");
-            } else {
-                print!("{}", count.to_string().bold().green());
-            }
+        let row: Vec<String> = (0..bounds.0)
+            .map(|x| {
+                let count = counts.get(&(x, y)).copied().unwrap_or(0);
+                match format {
+                    FrameFormat::Text if count == 0 => ".".to_string(),
+                    FrameFormat::Text => count.to_string(),
+                    FrameFormat::Pbm => ((count > 0) as u8).to_string(),
+                    FrameFormat::Pgm => count.to_string(),
+                }
+            })
+            .collect();
+        let separator = if matches!(format, FrameFormat::Text) {
+            ""
+        } else {
+            " "
+        };
+        frame.push_str(&row.join(separator));
+        frame.push('\n');
+    }
+    frame
+}
+
+// Arena size like 11x7
+fn parse_size(arg: &str) -> Option<(isize, isize)> {
+    let (w, h) = arg.split_once('x')?;
+    let (w, h) = (w.parse().ok()?, h.parse().ok()?);
+    (w > 0 && h > 0).then_some((w, h))
+}
+
+// Trailing tool arguments: an output format and/or an arena size like 11x7
+fn parse_frame_options(
+    args: &[String],
+    robots: &[Robot],
+) -> Result<(FrameFormat, (isize, isize)), ToolError> {
+    let mut format = FrameFormat::Text;
+    let mut bounds = detect_bounds(robots);
+    for arg in args {
+        match arg.as_str() {
+            "text" => format = FrameFormat::Text,
+            "pbm" => format = FrameFormat::Pbm,
+            "pgm" => format = FrameFormat::Pgm,
+            _ => match parse_size(arg) {
+                Some(size) => bounds = size,
+                None => return Err(ToolError::BadArgs(format!("Unknown option \"{arg}\""))),
+            },
         }
-        println!("");
     }
+    Ok((format, bounds))
+}
+
+fn parse_number(args: &[String], idx: usize, name: &str) -> Result<usize, ToolError> {
+    let arg = args
+        .get(idx)
+        .ok_or_else(|| ToolError::BadArgs(format!("Missing {name}")))?;
+    arg.parse()
+        .map_err(|_e| ToolError::BadArgs(format!("Could not parse {name} \"{arg}\"")))
 }
 
 fn pos_variance(coords: Vec<f64>) -> f64 {
@@ -84,12 +175,156 @@ fn pos_variance(coords: Vec<f64>) -> f64 {
     num / (denom as f64)
 }
 
+fn axis_position(position: isize, velocity: isize, time: usize, bound: isize) -> isize {
+    (position + time as isize * velocity).rem_euclid(bound)
+}
+
+// Shannon entropy of the distribution of coordinates
+fn pos_entropy(coords: &[isize], bound: isize) -> f64 {
+    let mut counts = vec![0usize; bound as usize];
+    for c in coords {
+        counts[*c as usize] += 1;
+    }
+    let n = coords.len() as f64;
+    counts
+        .into_iter()
+        .filter(|c| *c > 0)
+        .map(|c| {
+            let p = c as f64 / n;
+            -p * p.ln()
+        })
+        .sum()
+}
+
+// Size of the largest 4-connected group of occupied tiles
+fn largest_cluster(positions: &[(isize, isize)], bounds: (isize, isize)) -> usize {
+    let (width, height) = (bounds.0 as usize, bounds.1 as usize);
+    let mut occupied = vec![false; width * height];
+    for (x, y) in positions {
+        occupied[*y as usize * width + *x as usize] = true;
+    }
+    let mut largest = 0;
+    for start in 0..occupied.len() {
+        if !occupied[start] {
+            continue;
+        }
+        occupied[start] = false;
+        let mut to_visit = vec![start];
+        let mut size = 0;
+        while let Some(idx) = to_visit.pop() {
+            size += 1;
+            let (x, y) = (idx % width, idx / width);
+            let nbrs = [
+                (x > 0).then(|| idx - 1),
+                (x + 1 < width).then(|| idx + 1),
+                (y > 0).then(|| idx - width),
+                (y + 1 < height).then(|| idx + width),
+            ];
+            for nbr in nbrs.into_iter().flatten() {
+                if occupied[nbr] {
+                    occupied[nbr] = false;
+                    to_visit.push(nbr);
+                }
+            }
+        }
+        largest = largest.max(size);
+    }
+    largest
+}
+
+// How we judge which second looks most like a picture
+#[derive(Clone, Copy)]
+enum Scoring {
+    Variance,
+    Entropy,
+    Cluster,
+}
+
+impl Scoring {
+    // Lower is more ordered
+    fn axis_score(&self, coords: Vec<isize>, bound: isize) -> f64 {
+        match self {
+            Scoring::Variance => pos_variance(coords.into_iter().map(|c| c as f64).collect()),
+            Scoring::Entropy => pos_entropy(&coords, bound),
+            Scoring::Cluster => unreachable!("Clusters can't be scored one axis at a time"),
+        }
+    }
+}
+
+// Smallest t with t = a mod m and t = b mod n
+fn chinese_remainder(a: usize, m: usize, b: usize, n: usize) -> Option<usize> {
+    let (g, m_inv, _) = extended_gcd(m as i128, n as i128);
+    if g != 1 {
+        return None;
+    }
+    // t = a + m * k where m * k = b - a mod n
+    let k = ((b as i128 - a as i128) * m_inv).rem_euclid(n as i128);
+    Some(a + m * k as usize)
+}
+
+// Each robot's x coordinate repeats every width seconds and its y every height seconds.
+// The tree is a big picture so it makes both coordinates unusually ordered: find the most
+// ordered time for each axis separately and combine them with the CRT.
+// Clusters aren't separable, so for those we try every second in the full period.
+fn find_tree(robots: &[Robot], bounds: (isize, isize), scoring: Scoring) -> usize {
+    let (width, height) = (bounds.0 as usize, bounds.1 as usize);
+    let best_axis_time = |period: usize, axis: fn(&Robot) -> (isize, isize), bound: isize| {
+        (0..period)
+            .map(|t| {
+                let coords = robots
+                    .iter()
+                    .map(|r| {
+                        let (p, v) = axis(r);
+                        axis_position(p, v, t, bound)
+                    })
+                    .collect();
+                (t, scoring.axis_score(coords, bound))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap()
+            .0
+    };
+    let positions_at = |t: usize| -> Vec<(isize, isize)> {
+        robots
+            .iter()
+            .map(|r| {
+                (
+                    axis_position(r.position.0, r.velocity.0, t, bounds.0),
+                    axis_position(r.position.1, r.velocity.1, t, bounds.1),
+                )
+            })
+            .collect()
+    };
+    let full_period = || 0..(width * height);
+
+    if !matches!(scoring, Scoring::Cluster) {
+        let tx = best_axis_time(width, |r| (r.position.0, r.velocity.0), bounds.0);
+        let ty = best_axis_time(height, |r| (r.position.1, r.velocity.1), bounds.1);
+        if let Some(t) = chinese_remainder(tx, width, ty, height) {
+            return t;
+        }
+        // Arena sides share a factor, so fall back to scoring both axes at every time
+        return full_period()
+            .map(|t| {
+                let (xs, ys): (Vec<_>, Vec<_>) = positions_at(t).into_iter().unzip();
+                (
+                    t,
+                    scoring.axis_score(xs, bounds.0) + scoring.axis_score(ys, bounds.1),
+                )
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap()
+            .0;
+    }
+    full_period()
+        .max_by_key(|t| largest_cluster(&positions_at(*t), bounds))
+        .unwrap()
+}
+
 impl ProblemSolution for Solution {
     fn solve_a(&self, input: &str) -> Option<String> {
-        let width = 101;
-        let height = 103;
-        let bounds = (width, height);
         let mut robots = parse(input);
+        let bounds = detect_bounds(&robots);
 
         for r in robots.iter_mut() {
             r.advance(100, bounds);
@@ -105,34 +340,90 @@ impl ProblemSolution for Solution {
     }
 
     fn solve_b(&self, input: &str) -> Option<String> {
-        let mut t = 0;
-        let width = 101;
-        let height = 103;
-        let bounds = (width, height);
+        let robots = parse(input);
+        let bounds = detect_bounds(&robots);
+        let t = find_tree(&robots, bounds, Scoring::Variance);
+        Some(t.to_string())
+    }
+
+    // tree [variance|entropy|cluster] [WxH] : find the tree with the given scoring function
+    // frame <t> [text|pbm|pgm] [WxH] : robot positions after t seconds
+    // frames <start> <end> <dir> [text|pbm|pgm] [WxH] : one file per second from start to end
+    fn run_tool(&self, input: &str, args: &[String]) -> Result<String, ToolError> {
         let mut robots = parse(input);
-        let trigger = 400f64;
-
-        loop {
-            let x_poses = robots
-                .iter()
-                .map(|r| r.position.0 as f64)
-                .collect::<Vec<f64>>();
-            let x_var = pos_variance(x_poses);
-            let y_poses = robots
-                .iter()
-                .map(|r| r.position.1 as f64)
-                .collect::<Vec<f64>>();
-            let y_var = pos_variance(y_poses);
-            if x_var < trigger && y_var < trigger {
-                break;
+        match args[0].as_str() {
+            "tree" => {
+                let mut scoring = Scoring::Variance;
+                let mut bounds = detect_bounds(&robots);
+                for arg in &args[1..] {
+                    match (arg.as_str(), parse_size(arg)) {
+                        ("variance", _) => scoring = Scoring::Variance,
+                        ("entropy", _) => scoring = Scoring::Entropy,
+                        ("cluster", _) => scoring = Scoring::Cluster,
+                        (_, Some(size)) => bounds = size,
+                        _ => return Err(ToolError::BadArgs(format!("Unknown option \"{arg}\""))),
+                    }
+                }
+                let t = find_tree(&robots, bounds, scoring);
+                for r in robots.iter_mut() {
+                    r.advance(t, bounds);
+                }
+                Ok(format!(
+                    "After {t} seconds\n{}",
+                    render_frame(&robots, bounds, FrameFormat::Text)
+                ))
+            }
+            "frame" => {
+                let t = parse_number(args, 1, "time")?;
+                let (format, bounds) = parse_frame_options(&args[2..], &robots)?;
+                for r in robots.iter_mut() {
+                    r.advance(t, bounds);
+                }
+                Ok(render_frame(&robots, bounds, format))
             }
-            for r in robots.iter_mut() {
-                r.advance(1, bounds);
+            "frames" => {
+                let start = parse_number(args, 1, "start time")?;
+                let end = parse_number(args, 2, "end time")?;
+                let dir = args
+                    .get(3)
+                    .ok_or_else(|| ToolError::BadArgs("Missing output directory".to_string()))?;
+                let (format, bounds) = parse_frame_options(&args[4..], &robots)?;
+                fs::create_dir_all(dir).map_err(|e| ToolError::Io(e.to_string()))?;
+                for r in robots.iter_mut() {
+                    r.advance(start, bounds);
+                }
+                for t in start..=end {
+                    let path = format!("{dir}/frame_{t:05}.{}", format.extension());
+                    fs::write(&path, render_frame(&robots, bounds, format))
+                        .map_err(|e| ToolError::Io(e.to_string()))?;
+                    for r in robots.iter_mut() {
+                        r.advance(1, bounds);
+                    }
+                }
+                Ok(format!("Wrote {} frames to {dir}", (start..=end).count()))
             }
-            t += 1;
+            _ => Err(ToolError::Unknown),
         }
+    }
 
-        display_robots(&robots, bounds);
-        Some(t.to_string())
+    // size robots in the full 101 x 103 arena
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        let (width, height) = KNOWN_ARENAS[1];
+        let mut output = String::new();
+        for _ in 0..size {
+            let position = (
+                rng.range(0..=width as usize - 1),
+                rng.range(0..=height as usize - 1),
+            );
+            let velocity = (
+                rng.range(0..=198) as isize - 99,
+                rng.range(0..=198) as isize - 99,
+            );
+            output.push_str(&format!(
+                "p={},{} v={},{}\n",
+                position.0, position.1, velocity.0, velocity.1
+            ));
+        }
+        Some(output)
     }
 }
@@ -1,5 +1,10 @@
 use std::fmt::Display;
+use std::io::{self, IsTerminal, Read, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
 
+use crate::problem::ToolError;
+use crate::rng::Rng;
 use crate::ProblemSolution;
 #[allow(unused_imports)]
 use aoc_parse::{parser, prelude::*};
@@ -16,19 +21,22 @@ enum State {
     Robot,
 }
 
-fn move_to_delta(mv: char) -> (isize, isize) {
+fn move_to_delta(mv: char) -> Option<(isize, isize)> {
     match mv {
-        'v' => (1, 0),
-        '>' => (0, 1),
-        '^' => (-1, 0),
-        '<' => (0, -1),
-        _ => panic!(),
+        'v' => Some((1, 0)),
+        '>' => Some((0, 1)),
+        '^' => Some((-1, 0)),
+        '<' => Some((0, -1)),
+        _ => None,
     }
 }
 
 struct Warehouse {
     grid: Vec<Vec<State>>,
     robot_pos: (usize, usize),
+    // Previous state of every cell changed during the current do_move, so moves can be undone.
+    // None when not recording, as when solving
+    journal: Option<Vec<((usize, usize), State)>>,
 }
 
 impl Display for Warehouse {
@@ -48,7 +56,7 @@ impl Display for Warehouse {
                     }
                 )?
             }
-            writeln!(f, "")?;
+            writeln!(f)?;
         }
         Ok(())
     }
@@ -60,13 +68,16 @@ impl Warehouse {
     }
 
     fn set_state(&mut self, pos: (usize, usize), state: State) {
+        if let Some(journal) = &mut self.journal {
+            journal.push((pos, self.grid[pos.0][pos.1]));
+        }
         self.grid[pos.0][pos.1] = state;
     }
 
     fn try_bump(&mut self, pos: (usize, usize), delta: (isize, isize)) -> bool {
         match self.get_state(pos) {
-            State::Empty => return true,
-            State::Wall => return false,
+            State::Empty => true,
+            State::Wall => false,
             State::Box | State::Robot => {
                 let target = (pos.0 as isize + delta.0, pos.1 as isize + delta.1);
                 let target = (target.0 as usize, target.1 as usize);
@@ -78,7 +89,8 @@ impl Warehouse {
                     false
                 }
             }
-            _ => panic!(),
+            // Only the wide warehouse has box halves, and it uses get_upstream instead
+            State::BoxLeft | State::BoxRight => unreachable!("try_bump on a box half"),
         }
     }
 
@@ -91,7 +103,8 @@ impl Warehouse {
         let target = (target.0 as usize, target.1 as usize);
         match self.get_state(pos) {
             State::Empty => vec![],
-            State::Wall => panic!(),
+            // get_upstream stops at walls before asking what is behind them
+            State::Wall => unreachable!("nothing is upstream of a wall"),
             State::Box | State::Robot => {
                 vec![target]
             }
@@ -146,7 +159,7 @@ impl Warehouse {
     }
 
     fn do_move_a(&mut self, mv: char) {
-        let delta = move_to_delta(mv);
+        let delta = move_to_delta(mv).expect("moves are validated when parsing");
         if self.try_bump(self.robot_pos, delta) {
             let new_pos = (
                 self.robot_pos.0 as isize + delta.0,
@@ -157,7 +170,7 @@ impl Warehouse {
     }
 
     fn do_move_b(&mut self, mv: char) {
-        let delta = move_to_delta(mv);
+        let delta = move_to_delta(mv).expect("moves are validated when parsing");
         if let Some(stream) = self.get_upstream(self.robot_pos, delta) {
             self.move_upstream(stream, delta);
             let new_pos = (
@@ -177,6 +190,28 @@ impl Warehouse {
             .sum()
     }
 
+    // Make a move in either the normal or the wide warehouse, returning how to undo it
+    fn do_move(&mut self, mv: char, wide: bool) -> Undo {
+        self.journal = Some(vec![]);
+        let robot_pos = self.robot_pos;
+        if wide {
+            self.do_move_b(mv);
+        } else {
+            self.do_move_a(mv);
+        }
+        Undo {
+            changes: self.journal.take().unwrap_or_default(),
+            robot_pos,
+        }
+    }
+
+    fn undo(&mut self, undo: Undo) {
+        for (pos, state) in undo.changes.into_iter().rev() {
+            self.grid[pos.0][pos.1] = state;
+        }
+        self.robot_pos = undo.robot_pos;
+    }
+
     fn duplicate_grid(self) -> Self {
         let robot_pos = (self.robot_pos.0, self.robot_pos.1 * 2);
         let grid = self
@@ -189,35 +224,369 @@ impl Warehouse {
                         State::Wall => vec![State::Wall, State::Wall],
                         State::Box => vec![State::BoxLeft, State::BoxRight],
                         State::Robot => vec![State::Robot, State::Empty],
-                        _ => panic!(),
+                        State::BoxLeft | State::BoxRight => unreachable!("already wide"),
                     })
                     .collect()
             })
             .collect();
-        Self { grid, robot_pos }
+        Self {
+            grid,
+            robot_pos,
+            journal: None,
+        }
     }
 }
 
-fn parse(input: &str) -> (Warehouse, Vec<char>) {
-    let grid_parser = parser!(
-        lines({
-            "#" => State::Wall,
-            "." => State::Empty,
-            "O" => State::Box,
-            "@" => State::Robot
-        }+)
+struct Undo {
+    changes: Vec<((usize, usize), State)>,
+    robot_pos: (usize, usize),
+}
+
+// Puts the terminal into raw-ish mode for as long as it lives: keys arrive unbuffered and
+// unechoed, and reads give up after a tenth of a second so we can animate between key presses
+struct RawTerminal {
+    saved_settings: String,
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("stty failed, is stdin a terminal?"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+impl RawTerminal {
+    fn enable() -> io::Result<Self> {
+        if !cfg!(unix) || !io::stdin().is_terminal() {
+            return Err(io::Error::other(
+                "this tool needs stdin to be an interactive Unix terminal",
+            ));
+        }
+        let saved_settings = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "min", "0", "time", "1"])?;
+        Ok(Self { saved_settings })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.saved_settings]);
+    }
+}
+
+enum Key {
+    // As a move character
+    Arrow(char),
+    Char(char),
+}
+
+// None if no key was pressed before the read timed out
+fn read_key() -> Option<Key> {
+    let mut buf = [0; 3];
+    let n = io::stdin().read(&mut buf).ok()?;
+    match &buf[..n] {
+        [] => None,
+        [0x1b, b'[', b'A'] => Some(Key::Arrow('^')),
+        [0x1b, b'[', b'B'] => Some(Key::Arrow('v')),
+        [0x1b, b'[', b'C'] => Some(Key::Arrow('>')),
+        [0x1b, b'[', b'D'] => Some(Key::Arrow('<')),
+        [c, ..] => Some(Key::Char(*c as char)),
+    }
+}
+
+fn draw(warehouse: &Warehouse, status: &str, help: &str) {
+    // Clear the screen and move the cursor home before each frame
+    print!(
+        "\x1b[2J\x1b[H{warehouse}GPS sum: {}\n{status}\n{}\n",
+        warehouse.sum_gps().to_string().bold().cyan(),
+        help.dimmed()
     );
+    let _ = io::stdout().flush();
+}
+
+// Drive the robot with the arrow keys
+fn play(mut warehouse: Warehouse, wide: bool) -> usize {
+    let help = "arrows: move, u: undo, q: quit";
+    let mut history = vec![];
+    loop {
+        draw(&warehouse, &format!("Moves made: {}", history.len()), help);
+        let key = loop {
+            if let Some(key) = read_key() {
+                break key;
+            }
+        };
+        match key {
+            Key::Arrow(mv) => {
+                history.push(warehouse.do_move(mv, wide));
+            }
+            Key::Char('u') => {
+                if let Some(undo) = history.pop() {
+                    warehouse.undo(undo);
+                }
+            }
+            Key::Char('q') => break,
+            Key::Char(_) => {}
+        }
+    }
+    warehouse.sum_gps()
+}
+
+// Step through the move list, pausing and rewinding on request
+fn replay(mut warehouse: Warehouse, moves: &[char], wide: bool, delay: Duration) -> usize {
+    let help = "space: pause/resume, right: step forward, left: step back, q: quit";
+    let mut history: Vec<Undo> = vec![];
+    let mut paused = false;
+    let mut last_step = Instant::now();
+    loop {
+        let next = history.len();
+        let status = format!(
+            "Move {next}/{}{}{}",
+            moves.len(),
+            moves
+                .get(next)
+                .map(|mv| format!(", next {mv}"))
+                .unwrap_or_default(),
+            if paused { " (paused)" } else { "" }
+        );
+        draw(&warehouse, &status, help);
+        let step_forward = |warehouse: &mut Warehouse, history: &mut Vec<Undo>| {
+            if let Some(mv) = moves.get(history.len()) {
+                history.push(warehouse.do_move(*mv, wide));
+            }
+        };
+        match read_key() {
+            Some(Key::Char(' ')) => {
+                paused = !paused;
+                last_step = Instant::now();
+            }
+            Some(Key::Arrow('>')) => {
+                paused = true;
+                step_forward(&mut warehouse, &mut history);
+            }
+            Some(Key::Arrow('<')) => {
+                paused = true;
+                if let Some(undo) = history.pop() {
+                    warehouse.undo(undo);
+                }
+            }
+            Some(Key::Char('q')) => break,
+            _ => {}
+        }
+        if !paused {
+            // Key reads block for a tenth of a second, so catch up on any moves that fell due
+            while last_step.elapsed() >= delay && history.len() < moves.len() {
+                step_forward(&mut warehouse, &mut history);
+                last_step += delay;
+            }
+            if history.len() == moves.len() {
+                paused = true;
+            }
+        }
+    }
+    warehouse.sum_gps()
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Location {
+    line: usize,
+    col: usize,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.col)
+    }
+}
+
+#[derive(Debug)]
+struct ParseError {
+    location: Location,
+    message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}", self.message, self.location)
+    }
+}
 
-    let p = parser!(section(grid_parser) section(lines(any_char+)));
-    let (grid, moves) = p.parse(input).unwrap();
-    let moves = moves.into_iter().flatten().collect();
+// Like parse, but rejects anything that would otherwise panic later, and remembers
+// where each move came from
+fn parse_checked(input: &str) -> Result<(Warehouse, Vec<(char, Location)>), ParseError> {
+    let error = |line: usize, col: usize, message: String| ParseError {
+        location: Location {
+            line: line + 1,
+            col: col + 1,
+        },
+        message,
+    };
+    let mut lines = input.lines().enumerate();
+    let mut grid: Vec<Vec<State>> = vec![];
+    let mut robot_pos = None;
+    for (line, text) in lines.by_ref() {
+        if text.is_empty() {
+            break;
+        }
+        let mut row = vec![];
+        for (col, c) in text.chars().enumerate() {
+            let state = match c {
+                '#' => State::Wall,
+                '.' => State::Empty,
+                'O' => State::Box,
+                '@' => State::Robot,
+                _ => return Err(error(line, col, format!("Unexpected '{c}' in warehouse"))),
+            };
+            if matches!(state, State::Robot) {
+                if robot_pos.is_some() {
+                    return Err(error(line, col, "Second robot in warehouse".to_string()));
+                }
+                robot_pos = Some((grid.len(), col));
+            }
+            row.push(state);
+        }
+        if let Some(first) = grid.first() {
+            if row.len() != first.len() {
+                return Err(error(
+                    line,
+                    row.len().min(first.len()),
+                    format!("Row has {} cells, expected {}", row.len(), first.len()),
+                ));
+            }
+        }
+        grid.push(row);
+    }
+    if grid.is_empty() {
+        return Err(error(0, 0, "Missing warehouse".to_string()));
+    }
     let (height, width) = (grid.len(), grid[0].len());
-    let robot_pos = (0..height)
-        .flat_map(|i| (0..width).map(move |j| (i, j)))
-        .find(|pos| matches!(grid[pos.0][pos.1], State::Robot))
-        .unwrap();
-    let warehouse = Warehouse { grid, robot_pos };
-    (warehouse, moves)
+    // Walls all the way round mean moves never need bounds checks
+    for (i, row) in grid.iter().enumerate() {
+        for (j, cell) in row.iter().enumerate() {
+            let on_edge = i == 0 || j == 0 || i == height - 1 || j == width - 1;
+            if on_edge && !matches!(cell, State::Wall) {
+                return Err(error(i, j, "Warehouse edge is not a wall".to_string()));
+            }
+        }
+    }
+    let Some(robot_pos) = robot_pos else {
+        return Err(error(height - 1, 0, "No robot in warehouse".to_string()));
+    };
+
+    let mut moves = vec![];
+    for (line, text) in lines {
+        for (col, c) in text.chars().enumerate() {
+            if move_to_delta(c).is_none() {
+                return Err(error(line, col, format!("Unexpected move '{c}'")));
+            }
+            moves.push((
+                c,
+                Location {
+                    line: line + 1,
+                    col: col + 1,
+                },
+            ));
+        }
+    }
+    let warehouse = Warehouse {
+        grid,
+        robot_pos,
+        journal: None,
+    };
+    Ok((warehouse, moves))
+}
+
+fn parse(input: &str) -> (Warehouse, Vec<char>) {
+    let (warehouse, moves) = parse_checked(input).unwrap_or_else(|e| panic!("{e}"));
+    (warehouse, moves.into_iter().map(|(mv, _)| mv).collect())
+}
+
+// What every move has to preserve
+struct Invariants {
+    walls: Vec<(usize, usize)>,
+    n_boxes: usize,
+}
+
+impl Warehouse {
+    fn positions(&self, matching: fn(State) -> bool) -> Vec<(usize, usize)> {
+        let (height, width) = (self.grid.len(), self.grid[0].len());
+        (0..height)
+            .flat_map(|i| (0..width).map(move |j| (i, j)))
+            .filter(|pos| matching(self.get_state(*pos)))
+            .collect()
+    }
+
+    fn invariants(&self) -> Invariants {
+        Invariants {
+            walls: self.positions(|s| matches!(s, State::Wall)),
+            n_boxes: self
+                .positions(|s| matches!(s, State::Box | State::BoxLeft))
+                .len(),
+        }
+    }
+
+    // Describes the first broken invariant, if any
+    fn check(&self, invariants: &Invariants) -> Result<(), String> {
+        let robots = self.positions(|s| matches!(s, State::Robot));
+        if robots != [self.robot_pos] {
+            return Err(format!(
+                "Expected one robot at {:?}, found robots at {robots:?}",
+                self.robot_pos
+            ));
+        }
+        for (i, row) in self.grid.iter().enumerate() {
+            for (j, pair) in row.windows(2).enumerate() {
+                match pair {
+                    [State::BoxLeft, State::BoxRight] => {}
+                    [State::BoxLeft, _] => {
+                        return Err(format!("Unpaired box half at {:?}", (i, j)))
+                    }
+                    [_, State::BoxRight] => {
+                        return Err(format!("Unpaired box half at {:?}", (i, j + 1)))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if self.positions(|s| matches!(s, State::Wall)) != invariants.walls {
+            return Err("Walls have changed".to_string());
+        }
+        let n_boxes = self
+            .positions(|s| matches!(s, State::Box | State::BoxLeft))
+            .len();
+        if n_boxes != invariants.n_boxes {
+            return Err(format!(
+                "Expected {} boxes, found {n_boxes}",
+                invariants.n_boxes
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Makes every move, checking the invariants after each one
+fn check_moves(mut warehouse: Warehouse, moves: &[(char, Location)], wide: bool) -> String {
+    let invariants = warehouse.invariants();
+    if let Err(problem) = warehouse.check(&invariants) {
+        return format!("Initial warehouse: {problem}");
+    }
+    for (n, (mv, location)) in moves.iter().enumerate() {
+        warehouse.do_move(*mv, wide);
+        if let Err(problem) = warehouse.check(&invariants) {
+            return format!(
+                "{problem} after move {} ('{mv}' at {location})\n{warehouse}",
+                n + 1
+            );
+        }
+    }
+    format!(
+        "All {} moves kept every invariant, final GPS sum: {}",
+        moves.len(),
+        warehouse.sum_gps()
+    )
 }
 
 impl ProblemSolution for Solution {
@@ -227,7 +596,6 @@ impl ProblemSolution for Solution {
             warehouse.do_move_a(mv);
         }
         let answer = warehouse.sum_gps();
-        //println!("{}", warehouse);
         Some(answer.to_string())
     }
 
@@ -238,7 +606,81 @@ impl ProblemSolution for Solution {
             warehouse.do_move_b(mv);
         }
         let answer = warehouse.sum_gps();
-        //println!("{}", warehouse);
         Some(answer.to_string())
     }
+
+    // play [wide] : drive the robot around the warehouse with the arrow keys
+    // replay [wide] [delay_ms] : step through the move list, with pause and rewind
+    // check [wide] : validate the input and check the warehouse invariants after every move
+    fn run_tool(&self, input: &str, args: &[String]) -> Result<String, ToolError> {
+        if !["play", "replay", "check"].contains(&args[0].as_str()) {
+            return Err(ToolError::Unknown);
+        }
+        let mut wide = false;
+        let mut delay = None;
+        for arg in &args[1..] {
+            match arg.parse() {
+                _ if arg == "wide" => wide = true,
+                Ok(ms) if args[0] == "replay" && delay.is_none() => delay = Some(ms),
+                _ => return Err(ToolError::BadArgs(format!("Unexpected \"{arg}\""))),
+            }
+        }
+        let (mut warehouse, moves) =
+            parse_checked(input).map_err(|e| ToolError::BadArgs(format!("Invalid input: {e}")))?;
+        if wide {
+            warehouse = warehouse.duplicate_grid();
+        }
+        if args[0] == "check" {
+            return Ok(check_moves(warehouse, &moves, wide));
+        }
+        let moves: Vec<char> = moves.into_iter().map(|(mv, _)| mv).collect();
+        let _terminal = RawTerminal::enable().map_err(|e| ToolError::Io(e.to_string()))?;
+        let gps = if args[0] == "play" {
+            play(warehouse, wide)
+        } else {
+            replay(
+                warehouse,
+                &moves,
+                wide,
+                Duration::from_millis(delay.unwrap_or(100)),
+            )
+        };
+        Ok(format!("Final GPS sum: {gps}"))
+    }
+
+    // A size x size walled warehouse, a tenth walls and a fifth boxes, then 8 * size^2 moves
+    // in lines of 1000
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        let size = size.max(3);
+        let mut grid: Vec<Vec<char>> = (0..size)
+            .map(|i| {
+                (0..size)
+                    .map(|j| {
+                        if i == 0 || j == 0 || i == size - 1 || j == size - 1 || rng.one_in(10) {
+                            '#'
+                        } else if rng.one_in(5) {
+                            'O'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        grid[rng.range(1..=size - 2)][rng.range(1..=size - 2)] = '@';
+        let mut output: String = grid
+            .into_iter()
+            .map(|row| row.into_iter().chain(['\n']).collect::<String>())
+            .collect();
+        output.push('\n');
+        let moves = ['^', 'v', '<', '>'];
+        let n_moves = 8 * size * size;
+        for idx in 0..n_moves {
+            output.push(*rng.pick(&moves));
+            if idx % 1000 == 999 || idx == n_moves - 1 {
+                output.push('\n');
+            }
+        }
+        Some(output)
+    }
 }
@@ -1,11 +1,16 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::iter;
 
+use crate::problem::ToolError;
+use crate::rng::Rng;
 use crate::ProblemSolution;
 #[allow(unused_imports)]
 use aoc_parse::{parser, prelude::*};
+use colored::Colorize;
 pub struct Solution {}
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 enum Direction {
     North,
     East,
@@ -21,7 +26,16 @@ impl Direction {
         [North, East, South, West]
     }
 
-    fn to_delta(&self) -> (isize, isize) {
+    fn name(self) -> &'static str {
+        match self {
+            North => "N",
+            East => "E",
+            South => "S",
+            West => "W",
+        }
+    }
+
+    fn to_delta(self) -> (isize, isize) {
         match self {
             North => (-1, 0),
             East => (0, 1),
@@ -49,7 +63,7 @@ impl Direction {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 struct ReindeerState {
     position: (usize, usize),
     direction: Direction,
@@ -126,91 +140,195 @@ impl Maze {
     }
 }
 
-struct Dijkstra<'a> {
-    maze: &'a Maze,
-    unvisited: FxHashSet<ReindeerState>,
-    paths: FxHashMap<ReindeerState, (FxHashSet<Vec<ReindeerState>>, usize)>,
-    current_node: ReindeerState,
-    current_paths: FxHashSet<Vec<ReindeerState>>,
-    current_cost: usize,
+// Shortest costs from the start to every reindeer state, remembering every predecessor that
+// achieves the shortest cost, so all optimal paths can be recovered without storing them
+struct Dijkstra {
+    costs: FxHashMap<ReindeerState, usize>,
+    predecessors: FxHashMap<ReindeerState, Vec<ReindeerState>>,
     target: (usize, usize),
 }
 
-// TODO: Very slow, could speed up by ignoring any paths longer than minimal path found to endpoint
-impl<'a> Dijkstra<'a> {
-    fn advance(&mut self) -> Option<(ReindeerState, (FxHashSet<Vec<ReindeerState>>, usize))> {
-        let moves = self.current_node.moves(self.maze);
-        for (next_state, add_cost) in moves {
-            let total_cost = self.current_cost + add_cost;
-            let (current_paths, current_dist) = self
-                .paths
-                .entry(next_state)
-                .or_insert((Default::default(), usize::MAX));
-            if total_cost > *current_dist {
+impl Dijkstra {
+    fn run(maze: &Maze, start: ReindeerState, target: (usize, usize)) -> Self {
+        let mut costs = FxHashMap::default();
+        let mut predecessors: FxHashMap<ReindeerState, Vec<ReindeerState>> = FxHashMap::default();
+        let mut queue = BinaryHeap::new();
+        costs.insert(start, 0);
+        queue.push(Reverse((0, start)));
+        let mut best = None;
+        while let Some(Reverse((cost, state))) = queue.pop() {
+            if best.is_some_and(|best| cost > best) {
+                break;
+            }
+            if costs[&state] < cost {
                 continue;
-            } else if total_cost < *current_dist {
-                *current_dist = total_cost;
-                *current_paths = self
-                    .current_paths
-                    .iter()
-                    .cloned()
-                    .map(|mut p| {
-                        p.push(next_state);
-                        p
-                    })
-                    .collect();
-            } else {
-                for p in self.current_paths.iter() {
-                    let mut new_p = p.clone();
-                    new_p.push(next_state);
-                    current_paths.insert(new_p);
+            }
+            if state.position == target {
+                best = Some(cost);
+                continue;
+            }
+            for (next_state, add_cost) in state.moves(maze) {
+                let total_cost = cost + add_cost;
+                let current_cost = costs.entry(next_state).or_insert(usize::MAX);
+                if total_cost < *current_cost {
+                    *current_cost = total_cost;
+                    predecessors.insert(next_state, vec![state]);
+                    queue.push(Reverse((total_cost, next_state)));
+                } else if total_cost == *current_cost {
+                    predecessors.entry(next_state).or_default().push(state);
                 }
             }
         }
-        self.unvisited.remove(&self.current_node);
-        self.unvisited
-            .iter()
-            .filter_map(|st| Some((*st, self.paths.get(st)?.clone())))
-            .min_by_key(|(_st, (_paths, dist))| *dist)
+        Self {
+            costs,
+            predecessors,
+            target,
+        }
     }
 
-    fn run(&mut self) {
-        while let Some((next_node, (next_paths, next_cost))) = self.advance() {
-            if next_node.position == self.target {
-                return;
+    fn best_cost(&self) -> Option<usize> {
+        Direction::all()
+            .into_iter()
+            .filter_map(|direction| {
+                self.costs.get(&ReindeerState {
+                    position: self.target,
+                    direction,
+                })
+            })
+            .min()
+            .copied()
+    }
+
+    // Every state on at least one optimal path, found by walking back from the cheapest ends
+    fn optimal_states(&self) -> FxHashSet<ReindeerState> {
+        let Some(best) = self.best_cost() else {
+            return FxHashSet::default();
+        };
+        let mut to_visit: Vec<_> = Direction::all()
+            .into_iter()
+            .map(|direction| ReindeerState {
+                position: self.target,
+                direction,
+            })
+            .filter(|state| self.costs.get(state) == Some(&best))
+            .collect();
+        let mut optimal = FxHashSet::default();
+        while let Some(state) = to_visit.pop() {
+            if optimal.insert(state) {
+                to_visit.extend(self.predecessors.get(&state).into_iter().flatten());
             }
-            self.current_node = next_node;
-            self.current_cost = next_cost;
-            self.current_paths = next_paths;
         }
+        optimal
     }
 
-    fn init(maze: &'a Maze, start: ReindeerState, target: (usize, usize)) -> Self {
-        let (height, width) = maze.bounds();
-        let unvisited = (0..height)
-            .flat_map(|i| {
-                (0..width)
-                    .filter(move |j| matches!(maze.location((i, *j)), Location::Empty))
-                    .flat_map(move |j| {
-                        Direction::all().map(|d| ReindeerState {
-                            position: (i, j),
-                            direction: d,
-                        })
-                    })
+    fn optimal_tiles(&self) -> FxHashSet<(usize, usize)> {
+        self.optimal_states()
+            .into_iter()
+            .map(|state| state.position)
+            .collect()
+    }
+
+    // Tiles where some optimal path turns on the spot
+    fn turn_points(&self) -> FxHashSet<(usize, usize)> {
+        self.optimal_states()
+            .into_iter()
+            .filter(|state| {
+                self.predecessors
+                    .get(state)
+                    .into_iter()
+                    .flatten()
+                    .any(|pred| pred.position == state.position)
             })
+            .map(|state| state.position)
+            .collect()
+    }
+
+    // The predecessor graph as JSON, one node per reachable state
+    fn to_json(&self) -> String {
+        let optimal = self.optimal_states();
+        let state_json = |state: &ReindeerState| {
+            format!(
+                "{{\"row\": {}, \"col\": {}, \"direction\": \"{}\"}}",
+                state.position.0,
+                state.position.1,
+                state.direction.name()
+            )
+        };
+        // The search stops once costs pass the best, so anything dearer is only a tentative
+        // cost, possibly with predecessors missing
+        let best_cost = self.best_cost();
+        let mut states: Vec<_> = self
+            .costs
+            .iter()
+            .filter(|(_state, cost)| best_cost.is_none_or(|best| **cost <= best))
             .collect();
-        let empty_path = vec![];
-        let mut current_paths = FxHashSet::default();
-        current_paths.insert(empty_path);
-        Self {
-            maze,
-            unvisited,
-            paths: Default::default(),
-            current_node: start,
-            current_cost: 0,
-            current_paths,
-            target,
+        states.sort_by_key(|(state, cost)| (**cost, **state));
+        let nodes: Vec<String> = states
+            .into_iter()
+            .map(|(state, cost)| {
+                let predecessors: Vec<String> = self
+                    .predecessors
+                    .get(state)
+                    .into_iter()
+                    .flatten()
+                    .map(state_json)
+                    .collect();
+                format!(
+                    "    {{\"state\": {}, \"cost\": {cost}, \"optimal\": {}, \"predecessors\": [{}]}}",
+                    state_json(state),
+                    optimal.contains(state),
+                    predecessors.join(", ")
+                )
+            })
+            .collect();
+        let best_cost = best_cost.map_or("null".to_string(), |cost| cost.to_string());
+        format!(
+            "{{\n  \"target\": {{\"row\": {}, \"col\": {}}},\n  \"best_cost\": {best_cost},\n  \"nodes\": [\n{}\n  ]\n}}\n",
+            self.target.0,
+            self.target.1,
+            nodes.join(",\n")
+        )
+    }
+}
+
+// The maze with every optimal tile highlighted and the tiles where optimal paths turn marked
+fn render(maze: &Maze, start: (usize, usize), algo: &Dijkstra) -> String {
+    let tiles = algo.optimal_tiles();
+    let turns = algo.turn_points();
+    let (height, width) = maze.bounds();
+    let mut output = String::new();
+    for i in 0..height {
+        for j in 0..width {
+            let cell = if (i, j) == start {
+                "S".bold().cyan()
+            } else if (i, j) == algo.target {
+                "E".bold().cyan()
+            } else if turns.contains(&(i, j)) {
+                "+".bold().yellow()
+            } else if tiles.contains(&(i, j)) {
+                "O".bold().green()
+            } else if matches!(maze.location((i, j)), Location::Wall) {
+                "#".dimmed()
+            } else {
+                ".".dimmed()
+            };
+            output.push_str(&cell.to_string());
         }
+        output.push('\n');
+    }
+    output.push_str(&format!(
+        "{} tiles on optimal paths, {} turn points\n",
+        tiles.len(),
+        turns.len()
+    ));
+    output
+}
+
+// The reindeer always starts facing east
+fn start_state(start: (usize, usize)) -> ReindeerState {
+    ReindeerState {
+        position: start,
+        direction: East,
     }
 }
 
@@ -240,51 +358,79 @@ fn parse(input: &str) -> (Maze, (usize, usize), (usize, usize)) {
     (Maze { grid }, start.unwrap(), end.unwrap())
 }
 
-// These solutions are embarassingly slow
+// A maze carved by a randomised depth-first search, so every tile can reach every other,
+// with one in ten of the remaining inner walls knocked through to give alternative routes.
+// Passages run along odd rows and columns, so the side is made odd
+fn generate_maze(size: usize, rng: &mut Rng) -> Vec<Vec<char>> {
+    let side = size.max(5) | 1;
+    let mut grid = vec![vec!['#'; side]; side];
+    let mut stack = vec![(side - 2, 1)];
+    grid[side - 2][1] = '.';
+    while let Some(&(i, j)) = stack.last() {
+        let mut unvisited: Vec<(usize, usize)> = [(0, 2), (2, 0), (0, -2), (-2, 0)]
+            .into_iter()
+            .map(|(di, dj): (isize, isize)| {
+                ((i as isize + di) as usize, (j as isize + dj) as usize)
+            })
+            .filter(|(ni, nj)| (1..side - 1).contains(ni) && (1..side - 1).contains(nj))
+            .filter(|(ni, nj)| grid[*ni][*nj] == '#')
+            .collect();
+        if unvisited.is_empty() {
+            stack.pop();
+            continue;
+        }
+        rng.shuffle(&mut unvisited);
+        let (ni, nj) = unvisited[0];
+        grid[(i + ni) / 2][(j + nj) / 2] = '.';
+        grid[ni][nj] = '.';
+        stack.push((ni, nj));
+    }
+    for (i, row) in grid.iter_mut().enumerate().take(side - 1).skip(1) {
+        for (j, cell) in row.iter_mut().enumerate().take(side - 1).skip(1) {
+            // Walls between two passage cells sit on exactly one odd coordinate
+            if (i % 2 == 1) != (j % 2 == 1) && *cell == '#' && rng.one_in(10) {
+                *cell = '.';
+            }
+        }
+    }
+    grid[side - 2][1] = 'S';
+    grid[1][side - 2] = 'E';
+    grid
+}
+
 impl ProblemSolution for Solution {
     fn solve_a(&self, input: &str) -> Option<String> {
         let (maze, start, end) = parse(input);
-        let mut algo = Dijkstra::init(
-            &maze,
-            ReindeerState {
-                position: start,
-                direction: East,
-            },
-            end,
-        );
-        algo.run();
-        let answer = algo
-            .paths
-            .into_iter()
-            .filter(|(k, _v)| k.position == end)
-            .map(|(_k, v)| v.1)
-            .min()
-            .unwrap();
+        let algo = Dijkstra::run(&maze, start_state(start), end);
+        let answer = algo.best_cost().unwrap();
         Some(answer.to_string())
     }
 
     fn solve_b(&self, input: &str) -> Option<String> {
         let (maze, start, end) = parse(input);
-        let mut algo = Dijkstra::init(
-            &maze,
-            ReindeerState {
-                position: start,
-                direction: East,
-            },
-            end,
-        );
-        algo.run();
-        let (_, (paths, _)) = algo
-            .paths
-            .into_iter()
-            .filter(|(k, _v)| k.position == end)
-            .min_by_key(|(_k, v)| v.1)
-            .unwrap();
-        let on_minimal: FxHashSet<_> = paths
+        let algo = Dijkstra::run(&maze, start_state(start), end);
+        let answer = algo.optimal_tiles().len();
+        Some(answer.to_string())
+    }
+
+    // render : the maze with every tile on an optimal path highlighted and turn points marked
+    // graph : the predecessor graph of the search as JSON, over the states it settled
+    fn run_tool(&self, input: &str, args: &[String]) -> Result<String, ToolError> {
+        let (maze, start, end) = parse(input);
+        let algo = Dijkstra::run(&maze, start_state(start), end);
+        match args[0].as_str() {
+            "render" => Ok(render(&maze, start, &algo)),
+            "graph" => Ok(algo.to_json()),
+            _ => Err(ToolError::Unknown),
+        }
+    }
+
+    // A size x size maze with S in the bottom left and E in the top right
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        let rows: Vec<String> = generate_maze(size, rng)
             .into_iter()
-            .flat_map(|p| p.into_iter().map(|s| s.position))
+            .map(|row| row.into_iter().chain(['\n']).collect())
             .collect();
-        let answer = on_minimal.len();
-        Some(answer.to_string())
+        Some(rows.concat())
     }
 }
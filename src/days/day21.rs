@@ -1,6 +1,279 @@
+use crate::problem::ToolError;
+use crate::rng::Rng;
 use crate::ProblemSolution;
 #[allow(unused_imports)]
 use aoc_parse::{parser, prelude::*};
+use rustc_hash::FxHashMap;
 pub struct Solution {}
 
-impl ProblemSolution for Solution {}
+struct Keypad {
+    keys: FxHashMap<char, (isize, isize)>,
+    gap: (isize, isize),
+}
+
+impl Keypad {
+    fn from_layout(layout: &[&str]) -> Self {
+        let mut keys = FxHashMap::default();
+        let mut gap = None;
+        for (i, row) in layout.iter().enumerate() {
+            for (j, c) in row.chars().enumerate() {
+                let pos = (i as isize, j as isize);
+                if c == ' ' {
+                    gap = Some(pos);
+                } else {
+                    keys.insert(c, pos);
+                }
+            }
+        }
+        Self {
+            keys,
+            gap: gap.unwrap(),
+        }
+    }
+
+    fn numeric() -> Self {
+        Self::from_layout(&["789", "456", "123", " 0A"])
+    }
+
+    fn directional() -> Self {
+        Self::from_layout(&[" ^A", "<v>"])
+    }
+
+    // The only routes worth considering go all the way in one axis and then the other
+    // (zig-zagging costs extra presses upstream), so there are at most two candidates.
+    // Each candidate ends by pressing A to activate the button we moved to.
+    fn routes(&self, from: char, to: char) -> Vec<String> {
+        let start = self.keys[&from];
+        let end = self.keys[&to];
+        let (di, dj) = (end.0 - start.0, end.1 - start.1);
+        let vertical = if di < 0 { '^' } else { 'v' };
+        let horizontal = if dj < 0 { '<' } else { '>' };
+        let vertical = vertical.to_string().repeat(di.unsigned_abs());
+        let horizontal = horizontal.to_string().repeat(dj.unsigned_abs());
+
+        let mut routes = vec![];
+        // Vertical first passes through the corner (end.0, start.1)
+        if (end.0, start.1) != self.gap {
+            routes.push(format!("{vertical}{horizontal}A"));
+        }
+        // Horizontal first passes through the corner (start.0, end.1)
+        if (start.0, end.1) != self.gap {
+            let route = format!("{horizontal}{vertical}A");
+            if !routes.contains(&route) {
+                routes.push(route);
+            }
+        }
+        routes
+    }
+}
+
+// A numeric keypad operated through a stack of `layers` robot-held directional keypads,
+// the last of which is pressed by a human
+struct KeypadChain {
+    numeric: Keypad,
+    directional: Keypad,
+    layers: usize,
+    cache: FxHashMap<(char, char, usize), usize>,
+}
+
+impl KeypadChain {
+    fn init(layers: usize) -> Self {
+        Self {
+            numeric: Keypad::numeric(),
+            directional: Keypad::directional(),
+            layers,
+            cache: FxHashMap::default(),
+        }
+    }
+
+    // Human presses needed to type seq on a directional keypad with depth robot layers above it
+    // Every robot arm starts (and, after each press, returns) on A
+    fn sequence_cost(&mut self, seq: &str, depth: usize) -> usize {
+        if depth == 0 {
+            return seq.len();
+        }
+        let mut previous = 'A';
+        let mut total = 0;
+        for c in seq.chars() {
+            total += self.move_cost(previous, c, depth);
+            previous = c;
+        }
+        total
+    }
+
+    fn move_cost(&mut self, from: char, to: char, depth: usize) -> usize {
+        if let Some(cost) = self.cache.get(&(from, to, depth)) {
+            return *cost;
+        }
+        let cost = self
+            .directional
+            .routes(from, to)
+            .into_iter()
+            .map(|route| self.sequence_cost(&route, depth - 1))
+            .min()
+            .unwrap();
+        self.cache.insert((from, to, depth), cost);
+        cost
+    }
+
+    fn code_cost(&mut self, code: &str) -> usize {
+        let mut previous = 'A';
+        let mut total = 0;
+        for c in code.chars() {
+            total += self
+                .numeric
+                .routes(previous, c)
+                .into_iter()
+                .map(|route| self.sequence_cost(&route, self.layers))
+                .min()
+                .unwrap();
+            previous = c;
+        }
+        total
+    }
+
+    fn expand_sequence(&mut self, seq: &str, depth: usize) -> String {
+        if depth == 0 {
+            return seq.to_string();
+        }
+        let mut previous = 'A';
+        let mut expanded = String::new();
+        for c in seq.chars() {
+            let best_route = self
+                .directional
+                .routes(previous, c)
+                .into_iter()
+                .min_by_key(|route| self.sequence_cost(route, depth - 1))
+                .unwrap();
+            expanded.push_str(&self.expand_sequence(&best_route, depth - 1));
+            previous = c;
+        }
+        expanded
+    }
+
+    // One concrete sequence of human presses achieving code_cost(code)
+    // Its length grows exponentially with the number of layers so only use this for small chains
+    fn shortest_sequence(&mut self, code: &str) -> String {
+        let mut previous = 'A';
+        let mut expanded = String::new();
+        for c in code.chars() {
+            let best_route = self
+                .numeric
+                .routes(previous, c)
+                .into_iter()
+                .min_by_key(|route| self.sequence_cost(route, self.layers))
+                .unwrap();
+            expanded.push_str(&self.expand_sequence(&best_route, self.layers));
+            previous = c;
+        }
+        expanded
+    }
+}
+
+fn numeric_part(code: &str) -> usize {
+    code.trim_end_matches('A').parse().unwrap()
+}
+
+fn total_complexity(codes: &[String], layers: usize) -> usize {
+    let mut chain = KeypadChain::init(layers);
+    codes
+        .iter()
+        .map(|code| chain.code_cost(code) * numeric_part(code))
+        .sum()
+}
+
+fn parse(input: &str) -> Vec<String> {
+    parser!(lines(string(alnum+))).parse(input).unwrap()
+}
+
+impl ProblemSolution for Solution {
+    fn solve_a(&self, input: &str) -> Option<String> {
+        let codes = parse(input);
+        let answer = total_complexity(&codes, 2);
+        Some(answer.to_string())
+    }
+
+    fn solve_b(&self, input: &str) -> Option<String> {
+        let codes = parse(input);
+        let answer = total_complexity(&codes, 25);
+        Some(answer.to_string())
+    }
+
+    // sequence [layers] : print a shortest human button sequence for each code (default 2 layers)
+    fn run_tool(&self, input: &str, args: &[String]) -> Result<String, ToolError> {
+        if args[0] != "sequence" {
+            return Err(ToolError::Unknown);
+        }
+        let layers = match args.get(1) {
+            Some(l) => l
+                .parse()
+                .map_err(|_e| ToolError::BadArgs(format!("Could not parse layers \"{l}\"")))?,
+            None => 2,
+        };
+        let codes = parse(input);
+        let mut chain = KeypadChain::init(layers);
+        let mut output = String::new();
+        for code in codes {
+            let sequence = chain.shortest_sequence(&code);
+            output.push_str(&format!("{code}: {sequence} ({})\n", sequence.len()));
+        }
+        Ok(output)
+    }
+
+    // size door codes of three digits and an A
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        let codes: Vec<String> = (0..size)
+            .map(|_| format!("{:03}A\n", rng.range(0..=999)))
+            .collect();
+        Some(codes.concat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE_CODES: [&str; 5] = ["029A", "980A", "179A", "456A", "379A"];
+
+    // The buttons pressed on keypad by an arm starting on A and driven by seq
+    fn press(keypad: &Keypad, seq: &str) -> String {
+        let mut pos = keypad.keys[&'A'];
+        let mut pressed = String::new();
+        for c in seq.chars() {
+            match c {
+                '^' => pos.0 -= 1,
+                'v' => pos.0 += 1,
+                '<' => pos.1 -= 1,
+                '>' => pos.1 += 1,
+                _ => {
+                    let (key, _pos) = keypad.keys.iter().find(|(_key, p)| **p == pos).unwrap();
+                    pressed.push(*key);
+                }
+            }
+            assert_ne!(pos, keypad.gap, "arm passed over the gap");
+        }
+        pressed
+    }
+
+    #[test]
+    fn shortest_sequences_match_the_example() {
+        let mut chain = KeypadChain::init(2);
+        for (code, length) in EXAMPLE_CODES.iter().zip([68, 60, 68, 64, 64]) {
+            let sequence = chain.shortest_sequence(code);
+            assert_eq!(sequence.len(), length);
+            assert_eq!(sequence.len(), chain.code_cost(code));
+            // Typed through both robots' directional keypads, it enters the code
+            let mut typed = sequence;
+            for _ in 0..2 {
+                typed = press(&chain.directional, &typed);
+            }
+            assert_eq!(press(&chain.numeric, &typed), *code);
+        }
+    }
+
+    #[test]
+    fn total_complexity_matches_the_example() {
+        let codes: Vec<String> = EXAMPLE_CODES.iter().map(|c| c.to_string()).collect();
+        assert_eq!(total_complexity(&codes, 2), 126384);
+    }
+}
@@ -1,6 +1,139 @@
+use crate::problem::ToolError;
+use crate::rng::Rng;
 use crate::ProblemSolution;
 #[allow(unused_imports)]
 use aoc_parse::{parser, prelude::*};
 pub struct Solution {}
 
-impl ProblemSolution for Solution {}
+const PRUNE_MASK: u32 = (1 << 24) - 1;
+const N_CHANGES: usize = 19;
+const N_SEQUENCES: usize = N_CHANGES * N_CHANGES * N_CHANGES * N_CHANGES;
+
+// Multiplying/dividing by powers of two and pruning mod 2^24 is just shifting and masking
+fn evolve(secret: u32) -> u32 {
+    let secret = (secret ^ (secret << 6)) & PRUNE_MASK;
+    let secret = secret ^ (secret >> 5);
+    (secret ^ (secret << 11)) & PRUNE_MASK
+}
+
+// Yields the initial secret followed by every evolution of it
+struct SecretNumbers(u32);
+
+impl Iterator for SecretNumbers {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.0;
+        self.0 = evolve(current);
+        Some(current)
+    }
+}
+
+// A window of 4 changes, each in -9..=9, packed as a base 19 number
+// Pushing a new change drops the oldest one
+fn push_change(index: usize, change: i8) -> usize {
+    (index * N_CHANGES + (change + 9) as usize) % N_SEQUENCES
+}
+
+fn unpack_changes(mut index: usize) -> [i8; 4] {
+    let mut changes = [0; 4];
+    for c in changes.iter_mut().rev() {
+        *c = (index % N_CHANGES) as i8 - 9;
+        index /= N_CHANGES;
+    }
+    changes
+}
+
+struct Market {
+    // Total bananas earned by each change sequence, indexed by push_change encoding
+    bananas: Vec<u32>,
+    // Last buyer (plus one) to have sold at each sequence, so each buyer only sells once
+    last_buyer: Vec<u32>,
+    n_buyers: u32,
+}
+
+impl Market {
+    fn init() -> Self {
+        Self {
+            bananas: vec![0; N_SEQUENCES],
+            last_buyer: vec![0; N_SEQUENCES],
+            n_buyers: 0,
+        }
+    }
+
+    fn add_buyer(&mut self, secret: u32, n_prices: usize) {
+        self.n_buyers += 1;
+        let mut prices = SecretNumbers(secret)
+            .take(n_prices + 1)
+            .map(|s| (s % 10) as i8);
+        let mut previous = prices.next().unwrap();
+        let mut index = 0;
+        for (i, price) in prices.enumerate() {
+            index = push_change(index, price - previous);
+            previous = price;
+            // Need 4 changes before we have a full window
+            if i < 3 || self.last_buyer[index] == self.n_buyers {
+                continue;
+            }
+            self.last_buyer[index] = self.n_buyers;
+            self.bananas[index] += price as u32;
+        }
+    }
+
+    fn best_sequence(&self) -> ([i8; 4], u32) {
+        let (index, bananas) = self
+            .bananas
+            .iter()
+            .enumerate()
+            .max_by_key(|(_idx, bananas)| **bananas)
+            .unwrap();
+        (unpack_changes(index), *bananas)
+    }
+}
+
+fn run_market(secrets: &[u32]) -> Market {
+    let mut market = Market::init();
+    for secret in secrets {
+        market.add_buyer(*secret, 2000);
+    }
+    market
+}
+
+fn parse(input: &str) -> Vec<u32> {
+    parser!(lines(u32)).parse(input).unwrap()
+}
+
+impl ProblemSolution for Solution {
+    fn solve_a(&self, input: &str) -> Option<String> {
+        let secrets = parse(input);
+        let answer: u64 = secrets
+            .into_iter()
+            .map(|s| SecretNumbers(s).nth(2000).unwrap() as u64)
+            .sum();
+        Some(answer.to_string())
+    }
+
+    fn solve_b(&self, input: &str) -> Option<String> {
+        let secrets = parse(input);
+        let (_sequence, answer) = run_market(&secrets).best_sequence();
+        Some(answer.to_string())
+    }
+
+    // winner : print the change sequence that earns the most bananas
+    fn run_tool(&self, input: &str, args: &[String]) -> Result<String, ToolError> {
+        if args[0] != "winner" {
+            return Err(ToolError::Unknown);
+        }
+        let secrets = parse(input);
+        let (sequence, bananas) = run_market(&secrets).best_sequence();
+        Ok(format!("{sequence:?} earns {bananas} bananas"))
+    }
+
+    // size initial secret numbers
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        let secrets: Vec<String> = (0..size)
+            .map(|_| format!("{}\n", rng.range(1..=(1 << 24) - 1)))
+            .collect();
+        Some(secrets.concat())
+    }
+}
@@ -1,6 +1,77 @@
+use crate::graph::Graph;
+use crate::rng::Rng;
 use crate::ProblemSolution;
 #[allow(unused_imports)]
 use aoc_parse::{parser, prelude::*};
+use itertools::Itertools;
+use rustc_hash::FxHashSet;
 pub struct Solution {}
 
-impl ProblemSolution for Solution {}
+fn parse(input: &str) -> Graph {
+    let edges = parser!(lines(string(alpha+) "-" string(alpha+)))
+        .parse(input)
+        .unwrap();
+    let mut graph = Graph::default();
+    for (a, b) in edges {
+        graph.add_edge(&a, &b);
+    }
+    graph
+}
+
+const CLIQUE_SIZE: usize = 13;
+
+impl ProblemSolution for Solution {
+    fn solve_a(&self, input: &str) -> Option<String> {
+        let graph = parse(input);
+        let answer = graph
+            .triangles()
+            .filter(|tri| tri.iter().any(|n| graph.name(*n).starts_with('t')))
+            .count();
+        Some(answer.to_string())
+    }
+
+    fn solve_b(&self, input: &str) -> Option<String> {
+        let graph = parse(input);
+        let password = graph
+            .maximum_clique()
+            .into_iter()
+            .map(|n| graph.name(n))
+            .sorted()
+            .join(",");
+        Some(password)
+    }
+
+    // size computers (between 13 and 676) with two-letter names. Thirteen of them form a clique,
+    // and the rest of the links are random, about six per computer
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        let mut names: Vec<String> = ('a'..='z')
+            .flat_map(|a| ('a'..='z').map(move |b| format!("{a}{b}")))
+            .collect();
+        rng.shuffle(&mut names);
+        names.truncate(size.clamp(CLIQUE_SIZE, names.len()));
+        let n = names.len();
+        let mut edges: FxHashSet<(usize, usize)> = FxHashSet::default();
+        for a in 0..CLIQUE_SIZE {
+            for b in a + 1..CLIQUE_SIZE {
+                edges.insert((a, b));
+            }
+        }
+        for _ in 0..3 * n {
+            let (a, b) = (rng.range(0..=n - 1), rng.range(0..=n - 1));
+            if a != b {
+                edges.insert((a.min(b), a.max(b)));
+            }
+        }
+        let mut edges: Vec<(usize, usize)> = edges.into_iter().collect();
+        edges.sort();
+        rng.shuffle(&mut edges);
+        let lines: Vec<String> = edges
+            .into_iter()
+            .map(|(a, b)| {
+                let (a, b) = if rng.one_in(2) { (a, b) } else { (b, a) };
+                format!("{}-{}\n", names[a], names[b])
+            })
+            .collect();
+        Some(lines.concat())
+    }
+}
@@ -1,6 +1,349 @@
+use std::collections::VecDeque;
+
+use crate::problem::ToolError;
+use crate::rng::Rng;
 use crate::ProblemSolution;
 #[allow(unused_imports)]
 use aoc_parse::{parser, prelude::*};
+use itertools::Itertools;
+use rustc_hash::{FxHashMap, FxHashSet};
 pub struct Solution {}
 
-impl ProblemSolution for Solution {}
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Op {
+    And,
+    Or,
+    Xor,
+}
+
+impl Op {
+    fn apply(self, a: bool, b: bool) -> bool {
+        match self {
+            Op::And => a && b,
+            Op::Or => a || b,
+            Op::Xor => a ^ b,
+        }
+    }
+}
+
+struct Gate {
+    inputs: [String; 2],
+    op: Op,
+    output: String,
+}
+
+impl Gate {
+    fn reads_inputs(&self) -> bool {
+        self.inputs
+            .iter()
+            .all(|w| w.starts_with('x') || w.starts_with('y'))
+    }
+
+    fn reads_first_inputs(&self) -> bool {
+        self.reads_inputs() && self.inputs.iter().all(|w| &w[1..] == "00")
+    }
+}
+
+struct Circuit {
+    initial: Vec<(String, bool)>,
+    gates: Vec<Gate>,
+}
+
+impl Circuit {
+    // Kahn's algorithm: a gate is ready once both of its input wires have been driven.
+    // None if the circuit contains a cycle
+    fn topological_order(&self) -> Option<Vec<usize>> {
+        let mut waiting: FxHashMap<&str, Vec<usize>> = FxHashMap::default();
+        for (idx, gate) in self.gates.iter().enumerate() {
+            for input in gate.inputs.iter() {
+                waiting.entry(input).or_default().push(idx);
+            }
+        }
+        let mut missing_inputs: Vec<usize> = vec![2; self.gates.len()];
+        let mut driven: VecDeque<&str> = self.initial.iter().map(|(w, _)| w.as_str()).collect();
+        let mut order = vec![];
+        while let Some(wire) = driven.pop_front() {
+            for idx in waiting.get(wire).into_iter().flatten() {
+                missing_inputs[*idx] -= 1;
+                if missing_inputs[*idx] == 0 {
+                    order.push(*idx);
+                    driven.push_back(&self.gates[*idx].output);
+                }
+            }
+        }
+        (order.len() == self.gates.len()).then_some(order)
+    }
+
+    fn simulate(&self) -> FxHashMap<&str, bool> {
+        let mut values: FxHashMap<&str, bool> =
+            self.initial.iter().map(|(w, v)| (w.as_str(), *v)).collect();
+        for idx in self.topological_order().expect("Circuit contains a cycle") {
+            let gate = &self.gates[idx];
+            let value = gate.op.apply(
+                values[gate.inputs[0].as_str()],
+                values[gate.inputs[1].as_str()],
+            );
+            values.insert(&gate.output, value);
+        }
+        values
+    }
+
+    fn highest_output(&self) -> &str {
+        self.gates
+            .iter()
+            .map(|g| g.output.as_str())
+            .filter(|w| w.starts_with('z'))
+            .max()
+            .unwrap()
+    }
+
+    // Gates wired up as a ripple-carry adder satisfy, for each bit i > 0,
+    //   x_i XOR y_i -> s_i       s_i XOR c_{i-1} -> z_i
+    //   x_i AND y_i -> a_i       s_i AND c_{i-1} -> b_i       a_i OR b_i -> c_i
+    // with x_0 XOR y_0 -> z_0, x_0 AND y_0 -> c_0 and the final carry driving the highest z.
+    // Returns each output wire which breaks this template, with an explanation
+    fn adder_violations(&self) -> Vec<(&str, &'static str)> {
+        let mut consumers: FxHashMap<&str, Vec<Op>> = FxHashMap::default();
+        for gate in self.gates.iter() {
+            for input in gate.inputs.iter() {
+                consumers.entry(input).or_default().push(gate.op);
+            }
+        }
+        let consumed_by =
+            |wire: &str, op: Op| consumers.get(wire).is_some_and(|ops| ops.contains(&op));
+        let highest_output = self.highest_output();
+
+        let mut violations = vec![];
+        for gate in self.gates.iter() {
+            let output = gate.output.as_str();
+            let problem = if output == highest_output {
+                (gate.op != Op::Or).then_some("final carry should come from an OR")
+            } else if output.starts_with('z') && gate.op != Op::Xor {
+                Some("sum bit should come from an XOR")
+            } else {
+                match gate.op {
+                    Op::Xor if gate.reads_first_inputs() => {
+                        (output != "z00").then_some("first sum bit should be z00")
+                    }
+                    Op::Xor if gate.reads_inputs() => {
+                        (!consumed_by(output, Op::Xor)).then_some("half sum should feed an XOR")
+                    }
+                    Op::Xor => (!output.starts_with('z'))
+                        .then_some("XOR with carry should produce a sum bit"),
+                    Op::And if gate.reads_first_inputs() => None,
+                    Op::And => {
+                        (!consumed_by(output, Op::Or)).then_some("partial carry should feed an OR")
+                    }
+                    Op::Or => None,
+                }
+            };
+            if let Some(problem) = problem {
+                violations.push((output, problem));
+            }
+        }
+        violations.sort();
+        violations
+    }
+
+    fn to_dot(&self, highlight: &FxHashSet<&str>) -> String {
+        let mut dot = String::from("digraph circuit {\n  rankdir=LR;\n");
+        let wires: FxHashSet<&str> = self
+            .gates
+            .iter()
+            .flat_map(|g| g.inputs.iter().chain(std::iter::once(&g.output)))
+            .map(|w| w.as_str())
+            .collect();
+        for wire in wires.into_iter().sorted() {
+            let color = if highlight.contains(wire) {
+                "red"
+            } else if wire.starts_with('z') {
+                "blue"
+            } else {
+                "black"
+            };
+            dot.push_str(&format!("  {wire} [shape=ellipse, color={color}];\n"));
+        }
+        for (idx, gate) in self.gates.iter().enumerate() {
+            dot.push_str(&format!("  g{idx} [shape=box, label=\"{:?}\"];\n", gate.op));
+            for input in gate.inputs.iter() {
+                dot.push_str(&format!("  {input} -> g{idx};\n"));
+            }
+            dot.push_str(&format!("  g{idx} -> {};\n", gate.output));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn read_number(values: &FxHashMap<&str, bool>, prefix: char) -> u64 {
+    values
+        .iter()
+        .filter(|(w, v)| w.starts_with(prefix) && **v)
+        .map(|(w, _)| 1 << w[1..].parse::<u64>().unwrap())
+        .sum()
+}
+
+fn parse(input: &str) -> Circuit {
+    let wire = parser!(string(alnum+));
+    let op = parser!({"AND" => Op::And, "OR" => Op::Or, "XOR" => Op::Xor});
+    let p = parser!(
+        section(lines(w:wire ": " v:u8 => (w, v == 1)))
+        section(lines(
+            a:wire " " op:op " " b:wire " -> " output:wire => Gate { inputs: [a, b], op, output }
+        ))
+    );
+    let (initial, gates) = p.parse(input).unwrap();
+    Circuit { initial, gates }
+}
+
+// A correct ripple-carry adder over bits bits (at least 2), following the template in
+// adder_violations, with random names for the internal wires
+fn generate_adder(bits: usize, rng: &mut Rng) -> Circuit {
+    let mut used = FxHashSet::default();
+    let mut fresh_wire = |rng: &mut Rng| loop {
+        let wire: String = (0..3)
+            .map(|_| (b'a' + rng.range(0..=22) as u8) as char)
+            .collect();
+        if used.insert(wire.clone()) {
+            return wire;
+        }
+    };
+    let mut gates = vec![];
+    let mut gate = |a: String, op: Op, b: String, output: String| {
+        gates.push(Gate {
+            inputs: [a, b],
+            op,
+            output: output.clone(),
+        });
+        output
+    };
+    let (x, y, z) = (
+        |i: usize| format!("x{i:02}"),
+        |i: usize| format!("y{i:02}"),
+        |i: usize| format!("z{i:02}"),
+    );
+    gate(x(0), Op::Xor, y(0), z(0));
+    let mut carry = gate(x(0), Op::And, y(0), fresh_wire(rng));
+    for i in 1..bits {
+        let half_sum = gate(x(i), Op::Xor, y(i), fresh_wire(rng));
+        gate(half_sum.clone(), Op::Xor, carry.clone(), z(i));
+        let half_carry = gate(x(i), Op::And, y(i), fresh_wire(rng));
+        let carried = gate(half_sum, Op::And, carry, fresh_wire(rng));
+        let output = if i == bits - 1 {
+            z(bits)
+        } else {
+            fresh_wire(rng)
+        };
+        carry = gate(half_carry, Op::Or, carried, output);
+    }
+    let initial = (0..bits)
+        .flat_map(|i| [x(i), y(i)])
+        .map(|wire| (wire, rng.one_in(2)))
+        .collect();
+    Circuit { initial, gates }
+}
+
+fn format_circuit(circuit: &Circuit) -> String {
+    let mut output = String::new();
+    for (wire, value) in circuit.initial.iter() {
+        output.push_str(&format!("{wire}: {}\n", *value as u8));
+    }
+    output.push('\n');
+    for gate in circuit.gates.iter() {
+        let op = match gate.op {
+            Op::And => "AND",
+            Op::Or => "OR",
+            Op::Xor => "XOR",
+        };
+        output.push_str(&format!(
+            "{} {op} {} -> {}\n",
+            gate.inputs[0], gate.inputs[1], gate.output
+        ));
+    }
+    output
+}
+
+impl ProblemSolution for Solution {
+    fn solve_a(&self, input: &str) -> Option<String> {
+        let circuit = parse(input);
+        let values = circuit.simulate();
+        let answer = read_number(&values, 'z');
+        Some(answer.to_string())
+    }
+
+    fn solve_b(&self, input: &str) -> Option<String> {
+        let circuit = parse(input);
+        let answer = circuit
+            .adder_violations()
+            .into_iter()
+            .map(|(w, _)| w)
+            .dedup()
+            .join(",");
+        Some(answer)
+    }
+
+    // analyse : explain why each swapped wire breaks the adder template
+    // dot : export the circuit as Graphviz DOT, with swapped wires in red
+    fn run_tool(&self, input: &str, args: &[String]) -> Result<String, ToolError> {
+        let circuit = parse(input);
+        match args[0].as_str() {
+            "analyse" => Ok(circuit
+                .adder_violations()
+                .into_iter()
+                .map(|(w, problem)| format!("{w}: {problem}\n"))
+                .collect()),
+            "dot" => {
+                let highlight = circuit
+                    .adder_violations()
+                    .into_iter()
+                    .map(|(w, _)| w)
+                    .collect();
+                Ok(circuit.to_dot(&highlight))
+            }
+            _ => Err(ToolError::Unknown),
+        }
+    }
+
+    // An adder over size bits (between 4 and 63) with four pairs of gate outputs swapped,
+    // retrying the swaps until the circuit has no cycles and the adder check finds exactly them
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        let mut circuit = generate_adder(size.clamp(4, 63), rng);
+        loop {
+            let mut idxs: Vec<usize> = (0..circuit.gates.len()).collect();
+            rng.shuffle(&mut idxs);
+            idxs.truncate(8);
+            for pair in idxs.chunks(2) {
+                let output = circuit.gates[pair[0]].output.clone();
+                circuit.gates[pair[0]].output =
+                    std::mem::replace(&mut circuit.gates[pair[1]].output, output);
+            }
+            let mut swapped: Vec<&str> = idxs
+                .iter()
+                .map(|idx| circuit.gates[*idx].output.as_str())
+                .collect();
+            swapped.sort();
+            let found: Vec<&str> = circuit
+                .adder_violations()
+                .into_iter()
+                .map(|(wire, _)| wire)
+                .dedup()
+                .collect();
+            if circuit.topological_order().is_some() && found == swapped {
+                break;
+            }
+            // Swapping the same pairs again undoes them
+            for pair in idxs.chunks(2) {
+                let output = circuit.gates[pair[0]].output.clone();
+                circuit.gates[pair[0]].output =
+                    std::mem::replace(&mut circuit.gates[pair[1]].output, output);
+            }
+        }
+        rng.shuffle(&mut circuit.gates);
+        for gate in circuit.gates.iter_mut() {
+            if rng.one_in(2) {
+                gate.inputs.swap(0, 1);
+            }
+        }
+        Some(format_circuit(&circuit))
+    }
+}
@@ -1,6 +1,101 @@
+use crate::rng::Rng;
 use crate::ProblemSolution;
 #[allow(unused_imports)]
 use aoc_parse::{parser, prelude::*};
 pub struct Solution {}
 
-impl ProblemSolution for Solution {}
+const WIDTH: usize = 5;
+const HEIGHT: usize = 7;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Lock,
+    Key,
+}
+
+// Every cell of a schematic fits in a u64 (bit row * WIDTH + col)
+// so a lock and key overlap iff their masks share a bit
+struct Schematic {
+    kind: Kind,
+    mask: u64,
+}
+
+impl Schematic {
+    // Locks hang down from the top row, keys stand up from the bottom row
+    // Heights exclude the full base row
+    fn from_heights(kind: Kind, heights: [usize; WIDTH]) -> Self {
+        let mut mask = 0;
+        for (col, height) in heights.into_iter().enumerate() {
+            let rows = match kind {
+                Kind::Lock => 0..=height,
+                Kind::Key => (HEIGHT - 1 - height)..=(HEIGHT - 1),
+            };
+            for row in rows {
+                mask |= 1 << (row * WIDTH + col);
+            }
+        }
+        Self { kind, mask }
+    }
+
+    fn fits(&self, other: &Schematic) -> bool {
+        self.mask & other.mask == 0
+    }
+}
+
+fn parse_schematic(rows: Vec<Vec<bool>>) -> Schematic {
+    let kind = if rows[0].iter().all(|filled| *filled) {
+        Kind::Lock
+    } else {
+        Kind::Key
+    };
+    let mut heights = [0; WIDTH];
+    for (col, height) in heights.iter_mut().enumerate() {
+        *height = rows.iter().filter(|row| row[col]).count() - 1;
+    }
+    Schematic::from_heights(kind, heights)
+}
+
+fn parse(input: &str) -> Vec<Schematic> {
+    let p = parser!(sections(lines({"#" => true, "." => false}+)));
+    p.parse(input)
+        .unwrap()
+        .into_iter()
+        .map(parse_schematic)
+        .collect()
+}
+
+impl ProblemSolution for Solution {
+    fn solve_a(&self, input: &str) -> Option<String> {
+        let schematics = parse(input);
+        let (locks, keys): (Vec<_>, Vec<_>) =
+            schematics.into_iter().partition(|s| s.kind == Kind::Lock);
+        let answer = locks
+            .iter()
+            .flat_map(|lock| keys.iter().filter(|key| lock.fits(key)))
+            .count();
+        Some(answer.to_string())
+    }
+
+    // size locks and keys, with random pin heights
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        let schematics: Vec<String> = (0..size)
+            .map(|_| {
+                let kind = if rng.one_in(2) { Kind::Lock } else { Kind::Key };
+                let heights: Vec<usize> = (0..WIDTH).map(|_| rng.range(0..=HEIGHT - 2)).collect();
+                let mut schematic = String::new();
+                for row in 0..HEIGHT {
+                    for height in heights.iter() {
+                        let filled = match kind {
+                            Kind::Lock => row <= *height,
+                            Kind::Key => row >= HEIGHT - 1 - height,
+                        };
+                        schematic.push(if filled { '#' } else { '.' });
+                    }
+                    schematic.push('\n');
+                }
+                schematic
+            })
+            .collect();
+        Some(schematics.join("\n"))
+    }
+}
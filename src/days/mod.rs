@@ -29,15 +29,15 @@ mod day25;
 
 pub fn get_solution(problem: usize) -> Result<Box<dyn ProblemSolution>, AocRunError> {
     match problem {
-        01 => Ok(Box::new(day01::Solution {})),
-        02 => Ok(Box::new(day02::Solution {})),
-        03 => Ok(Box::new(day03::Solution {})),
-        04 => Ok(Box::new(day04::Solution {})),
-        05 => Ok(Box::new(day05::Solution {})),
-        06 => Ok(Box::new(day06::Solution {})),
-        07 => Ok(Box::new(day07::Solution {})),
-        08 => Ok(Box::new(day08::Solution {})),
-        09 => Ok(Box::new(day09::Solution {})),
+        1 => Ok(Box::new(day01::Solution {})),
+        2 => Ok(Box::new(day02::Solution {})),
+        3 => Ok(Box::new(day03::Solution {})),
+        4 => Ok(Box::new(day04::Solution {})),
+        5 => Ok(Box::new(day05::Solution {})),
+        6 => Ok(Box::new(day06::Solution {})),
+        7 => Ok(Box::new(day07::Solution {})),
+        8 => Ok(Box::new(day08::Solution {})),
+        9 => Ok(Box::new(day09::Solution {})),
         10 => Ok(Box::new(day10::Solution {})),
         11 => Ok(Box::new(day11::Solution {})),
         12 => Ok(Box::new(day12::Solution {})),
@@ -0,0 +1,130 @@
+use rustc_hash::{FxHashMap, FxHashSet};
+
+pub type NodeId = usize;
+
+// Undirected simple graph whose nodes are interned strings
+#[derive(Default)]
+pub struct Graph {
+    names: Vec<String>,
+    ids: FxHashMap<String, NodeId>,
+    adjacency: Vec<FxHashSet<NodeId>>,
+}
+
+impl Graph {
+    // Get the id of the node with this name, adding it if necessary
+    pub fn intern(&mut self, name: &str) -> NodeId {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id = self.names.len();
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        self.adjacency.push(FxHashSet::default());
+        id
+    }
+
+    pub fn add_edge(&mut self, a: &str, b: &str) {
+        let a = self.intern(a);
+        let b = self.intern(b);
+        if a == b {
+            return;
+        }
+        self.adjacency[a].insert(b);
+        self.adjacency[b].insert(a);
+    }
+
+    pub fn name(&self, id: NodeId) -> &str {
+        &self.names[id]
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = NodeId> {
+        0..self.names.len()
+    }
+
+    pub fn nbrs(&self, id: NodeId) -> &FxHashSet<NodeId> {
+        &self.adjacency[id]
+    }
+
+    pub fn has_edge(&self, a: NodeId, b: NodeId) -> bool {
+        self.adjacency[a].contains(&b)
+    }
+
+    // Every triangle exactly once, with ids in increasing order
+    pub fn triangles(&self) -> impl Iterator<Item = [NodeId; 3]> + '_ {
+        self.nodes().flat_map(move |a| {
+            self.nbrs(a)
+                .iter()
+                .copied()
+                .filter(move |b| *b > a)
+                .flat_map(move |b| {
+                    self.nbrs(b)
+                        .iter()
+                        .copied()
+                        .filter(move |c| *c > b && self.has_edge(a, *c))
+                        .map(move |c| [a, b, c])
+                })
+        })
+    }
+
+    pub fn maximum_clique(&self) -> Vec<NodeId> {
+        let mut best = vec![];
+        self.bron_kerbosch(
+            &mut vec![],
+            self.nodes().collect(),
+            FxHashSet::default(),
+            &mut best,
+        );
+        best
+    }
+
+    // Bron–Kerbosch with pivoting
+    // clique is the current clique, candidates can extend it, excluded have already been tried
+    fn bron_kerbosch(
+        &self,
+        clique: &mut Vec<NodeId>,
+        mut candidates: FxHashSet<NodeId>,
+        mut excluded: FxHashSet<NodeId>,
+        best: &mut Vec<NodeId>,
+    ) {
+        if candidates.is_empty() {
+            if excluded.is_empty() && clique.len() > best.len() {
+                *best = clique.clone();
+            }
+            return;
+        }
+        // Can't beat the best clique so far
+        if clique.len() + candidates.len() <= best.len() {
+            return;
+        }
+        // Any maximal clique contains the pivot or one of its non-neighbours
+        // so choose the pivot with the most neighbours amongst the candidates
+        let pivot = *candidates
+            .iter()
+            .chain(excluded.iter())
+            .max_by_key(|p| {
+                self.nbrs(**p)
+                    .iter()
+                    .filter(|n| candidates.contains(n))
+                    .count()
+            })
+            .unwrap();
+        let to_try: Vec<_> = candidates
+            .iter()
+            .copied()
+            .filter(|c| !self.has_edge(pivot, *c))
+            .collect();
+        for v in to_try {
+            let nbrs = self.nbrs(v);
+            clique.push(v);
+            self.bron_kerbosch(
+                clique,
+                candidates.intersection(nbrs).copied().collect(),
+                excluded.intersection(nbrs).copied().collect(),
+                best,
+            );
+            clique.pop();
+            candidates.remove(&v);
+            excluded.insert(v);
+        }
+    }
+}
@@ -5,11 +5,18 @@ use std::fs;
 use std::str::FromStr;
 
 use crate::days;
+use crate::problem::ToolError;
+use crate::rng::Rng;
 
 pub enum AocRunError {
     NoFile(String),
     UnregistedProblem(usize),
     BadRunCode(String),
+    UnregisteredTool(usize, String),
+    BadToolArgs(String),
+    ToolFailed(String),
+    NoGenerator(usize),
+    BadGeneratorArgs(String),
 }
 
 impl Display for AocRunError {
@@ -20,6 +27,17 @@ impl Display for AocRunError {
                 format!("No solution registered for problem {problem}")
             }
             AocRunError::BadRunCode(code) => format!("Could not parse run code \"{code}\""),
+            AocRunError::UnregisteredTool(problem, tool) => {
+                format!("No tool \"{tool}\" registered for problem {problem}")
+            }
+            AocRunError::BadToolArgs(explanation) => format!("Bad tool arguments: {explanation}"),
+            AocRunError::ToolFailed(explanation) => format!("Tool failed: {explanation}"),
+            AocRunError::NoGenerator(problem) => {
+                format!("No input generator registered for problem {problem}")
+            }
+            AocRunError::BadGeneratorArgs(explanation) => {
+                format!("Bad generator arguments: {explanation}")
+            }
         };
         explanation.bold().red().fmt(f)
     }
@@ -31,8 +49,10 @@ pub struct RunCode<const SOLVED: bool> {
     run_a: bool,
     run_b: bool,
     as_example: bool,
+    tool: Vec<String>,
     result_a: Option<String>,
     result_b: Option<String>,
+    tool_output: Option<String>,
 }
 
 impl RunCode<false> {
@@ -42,8 +62,18 @@ impl RunCode<false> {
             run_a: true,
             run_b: true,
             as_example: false,
+            tool: vec![],
             result_a: None,
             result_b: None,
+            tool_output: None,
+        }
+    }
+
+    // Instead of solving, run the tool described by args (e.g. ["render", "frames"])
+    pub fn with_tool(self, args: &[String]) -> Self {
+        Self {
+            tool: args.to_vec(),
+            ..self
         }
     }
 }
@@ -68,8 +98,10 @@ impl FromStr for RunCode<false> {
             run_a,
             run_b,
             as_example,
+            tool: vec![],
             result_a: None,
             result_b: None,
+            tool_output: None,
         })
     }
 }
@@ -89,6 +121,25 @@ impl RunCode<false> {
     pub fn run(self) -> Result<RunCode<true>, AocRunError> {
         let solution = days::get_solution(self.problem)?;
         let input = self.get_input()?;
+        if !self.tool.is_empty() {
+            let output = solution.run_tool(&input, &self.tool).map_err(|e| match e {
+                ToolError::Unknown => {
+                    AocRunError::UnregisteredTool(self.problem, self.tool[0].clone())
+                }
+                ToolError::BadArgs(explanation) => AocRunError::BadToolArgs(explanation),
+                ToolError::Io(explanation) => AocRunError::ToolFailed(explanation),
+            })?;
+            return Ok(RunCode {
+                problem: self.problem,
+                run_a: false,
+                run_b: false,
+                as_example: self.as_example,
+                tool: self.tool,
+                result_a: None,
+                result_b: None,
+                tool_output: Some(output),
+            });
+        }
         let result_a = self.run_a.then(|| solution.solve_a(&input)).flatten();
         let result_b = self.run_b.then(|| solution.solve_b(&input)).flatten();
         Ok(RunCode {
@@ -96,12 +147,40 @@ impl RunCode<false> {
             run_a: self.run_a,
             run_b: self.run_b,
             as_example: self.as_example,
+            tool: self.tool,
             result_a,
             result_b,
+            tool_output: None,
         })
     }
 }
 
+// Arguments after "gen": <problem> [size] [seed], with size defaulting to 100 and seed to 0
+pub fn generate_input(args: &[String]) -> Result<String, AocRunError> {
+    let parse_arg = |idx: usize, name: &str, default: u64| match args.get(idx) {
+        Some(arg) => arg.parse().map_err(|_e| {
+            AocRunError::BadGeneratorArgs(format!("Could not parse {name} \"{arg}\""))
+        }),
+        None => Ok(default),
+    };
+    let Some(problem) = args.first() else {
+        return Err(AocRunError::BadGeneratorArgs("Missing problem".to_string()));
+    };
+    let problem = problem.parse().map_err(|_e| {
+        AocRunError::BadGeneratorArgs(format!("Could not parse problem \"{problem}\""))
+    })?;
+    let size = parse_arg(1, "size", 100)? as usize;
+    if size == 0 {
+        return Err(AocRunError::BadGeneratorArgs(
+            "Size must be positive".to_string(),
+        ));
+    }
+    let mut rng = Rng::seeded(parse_arg(2, "seed", 0)?);
+    days::get_solution(problem)?
+        .generate(size, &mut rng)
+        .ok_or(AocRunError::NoGenerator(problem))
+}
+
 impl Display for RunCode<true> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if self.run_a {
@@ -114,10 +193,18 @@ impl Display for RunCode<true> {
         if self.run_b {
             let solution_str = match self.result_b.as_ref() {
                 Some(s) => s.to_string().bold().cyan(),
+                // There is never a part B on Christmas Day
+                None if self.problem == 25 => "Merry Christmas!".to_string().bold().green(),
                 None => "TODO".to_string().dimmed(),
             };
             writeln!(f, "B : {solution_str}")?
         }
+        if let Some(output) = self.tool_output.as_ref() {
+            write!(f, "{output}")?;
+            if !output.is_empty() && !output.ends_with('\n') {
+                writeln!(f)?
+            }
+        }
         Ok(())
     }
 }
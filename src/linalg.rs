@@ -0,0 +1,126 @@
+// Exact integer linear algebra, avoiding floating point and rationals
+
+pub enum LinearSolution {
+    // x_i = numerators[i] / denominator
+    Unique {
+        numerators: Vec<i128>,
+        denominator: i128,
+    },
+    Singular,
+}
+
+// Fraction-free Gauss-Jordan elimination (Bareiss) of an n x (n + 1) augmented matrix.
+// Every division is exact, so intermediate values stay integers, and at the end
+// every diagonal entry equals the (signed) determinant.
+pub fn bareiss_solve(mut augmented: Vec<Vec<i128>>) -> LinearSolution {
+    let n = augmented.len();
+    let mut previous_pivot = 1;
+    for k in 0..n {
+        let Some(pivot_row) = (k..n).find(|r| augmented[*r][k] != 0) else {
+            return LinearSolution::Singular;
+        };
+        augmented.swap(k, pivot_row);
+        let pivot = augmented[k][k];
+        for i in (0..n).filter(|i| *i != k) {
+            let factor = augmented[i][k];
+            for j in (0..=n).filter(|j| *j != k) {
+                augmented[i][j] =
+                    (pivot * augmented[i][j] - factor * augmented[k][j]) / previous_pivot;
+            }
+            augmented[i][k] = 0;
+        }
+        previous_pivot = pivot;
+    }
+    LinearSolution::Unique {
+        numerators: augmented.iter().map(|row| row[n]).collect(),
+        denominator: previous_pivot,
+    }
+}
+
+// Returns (g, x, y) with a * x + b * y = g = gcd(a, b)
+pub fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        (a, 1, 0)
+    } else {
+        let (g, x, y) = extended_gcd(b, a.rem_euclid(b));
+        (g, y, x - a.div_euclid(b) * y)
+    }
+}
+
+fn div_floor(a: i128, b: i128) -> i128 {
+    let (q, r) = (a / b, a % b);
+    if r != 0 && ((r < 0) != (b < 0)) {
+        q - 1
+    } else {
+        q
+    }
+}
+
+fn div_ceil(a: i128, b: i128) -> i128 {
+    -div_floor(-a, b)
+}
+
+// Cheapest (a, b) with p * a + q * b = t, 0 <= a <= max_a and 0 <= b <= max_b,
+// where each unit of a costs cost_a and each unit of b costs cost_b (both non-negative)
+pub fn cheapest_two_term_solution(
+    (p, q, t): (i128, i128, i128),
+    (cost_a, cost_b): (i128, i128),
+    max_a: Option<i128>,
+    max_b: Option<i128>,
+) -> Option<(i128, i128)> {
+    let in_range = |x: i128, max: Option<i128>| x >= 0 && max.is_none_or(|m| x <= m);
+    if p == 0 && q == 0 {
+        return (t == 0).then_some((0, 0));
+    }
+    if q == 0 {
+        return (t % p == 0 && in_range(t / p, max_a)).then_some((t / p, 0));
+    }
+    if p == 0 {
+        return (t % q == 0 && in_range(t / q, max_b)).then_some((0, t / q));
+    }
+
+    let (g, x, y) = extended_gcd(p, q);
+    if t % g != 0 {
+        return None;
+    }
+    // Every solution is a = a0 + step_a * k, b = b0 - step_b * k
+    let (a0, b0) = (x * (t / g), y * (t / g));
+    let (step_a, step_b) = (q / g, p / g);
+
+    // Each bound on a or b becomes a bound on k
+    let mut k_min: Option<i128> = None;
+    let mut k_max: Option<i128> = None;
+    let mut bound = |value: i128, step: i128, lower: i128, upper: Option<i128>| {
+        // lower <= value + step * k <= upper
+        let (lo, hi) = if step > 0 {
+            (
+                Some(div_ceil(lower - value, step)),
+                upper.map(|u| div_floor(u - value, step)),
+            )
+        } else {
+            (
+                upper.map(|u| div_ceil(u - value, step)),
+                Some(div_floor(lower - value, step)),
+            )
+        };
+        if let Some(lo) = lo {
+            k_min = Some(k_min.map_or(lo, |m| m.max(lo)));
+        }
+        if let Some(hi) = hi {
+            k_max = Some(k_max.map_or(hi, |m| m.min(hi)));
+        }
+    };
+    bound(a0, step_a, 0, max_a);
+    bound(b0, -step_b, 0, max_b);
+
+    // Cost is linear in k, so the minimum is at one end of the range
+    let slope = cost_a * step_a - cost_b * step_b;
+    let k = match (k_min, k_max) {
+        (Some(lo), Some(hi)) if lo > hi => return None,
+        (Some(lo), _) if slope >= 0 => lo,
+        (_, Some(hi)) if slope <= 0 => hi,
+        // Cost is unbounded below
+        _ => return None,
+    };
+    Some((a0 + step_a * k, b0 - step_b * k))
+}
@@ -1,9 +1,11 @@
 mod days;
+mod graph;
 mod io;
+mod linalg;
 mod problem;
+mod rng;
 
-use colored::Colorize;
-use io::{AocRunError, RunCode};
+use io::{generate_input, AocRunError, RunCode};
 use problem::ProblemSolution;
 use std::{env, str::FromStr};
 
@@ -16,14 +18,6 @@ fn print_solution(solution: &Result<RunCode<true>, AocRunError>) {
 
 pub fn main() {
     let args: Vec<_> = env::args().collect();
-    if args.len() > 2 {
-        println!(
-            "{}",
-            "Please provide at most 1 argument".to_string().bold().red()
-        );
-        return;
-    }
-
     if args.len() == 1 {
         for i in 1..=25 {
             println!("Day {}", i);
@@ -34,8 +28,13 @@ pub fn main() {
                 break;
             }
         }
+    } else if args[1] == "gen" {
+        match generate_input(&args[2..]) {
+            Ok(input) => print!("{input}"),
+            Err(e) => println!("{e}"),
+        }
     } else {
-        let run_code = RunCode::from_str(&args[1]);
+        let run_code = RunCode::from_str(&args[1]).map(|rc| rc.with_tool(&args[2..]));
         let solution = run_code.and_then(|rc| rc.run());
         print_solution(&solution);
     }
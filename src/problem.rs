@@ -1,3 +1,12 @@
+use crate::rng::Rng;
+
+pub enum ToolError {
+    Unknown,
+    BadArgs(String),
+    // The tool understood its arguments but couldn't do its job (file writes, terminal setup)
+    Io(String),
+}
+
 pub trait ProblemSolution {
     #[allow(unused_variables)]
     fn solve_a(&self, input: &str) -> Option<String> {
@@ -7,4 +16,15 @@ pub trait ProblemSolution {
     fn solve_b(&self, input: &str) -> Option<String> {
         None
     }
+    // Extra tools (visualisers, exporters, diagnostics) selected by the arguments after the run code
+    #[allow(unused_variables)]
+    fn run_tool(&self, input: &str, args: &[String]) -> Result<String, ToolError> {
+        Err(ToolError::Unknown)
+    }
+    // A random input in exactly the format parse expects, with size scaling it (grid side,
+    // number of lines, ...), for fuzzing and benchmarking
+    #[allow(unused_variables)]
+    fn generate(&self, size: usize, rng: &mut Rng) -> Option<String> {
+        None
+    }
 }
@@ -0,0 +1,39 @@
+// Small seeded pseudorandom generator (SplitMix64) for producing synthetic inputs
+// Not suitable for anything needing real randomness
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn seeded(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in range (up to negligible modulo bias)
+    pub fn range(&mut self, range: std::ops::RangeInclusive<usize>) -> usize {
+        let width = (range.end() - range.start() + 1) as u64;
+        range.start() + (self.next_u64() % width) as usize
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.range(0..=items.len() - 1)]
+    }
+
+    // One in n chance
+    pub fn one_in(&mut self, n: usize) -> bool {
+        self.range(1..=n) == 1
+    }
+
+    // Fisher-Yates
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for idx in (1..items.len()).rev() {
+            items.swap(idx, self.range(0..=idx));
+        }
+    }
+}